@@ -1,5 +1,15 @@
 fn main() {
-    slint_build::compile("ui/main_window.slint").unwrap();
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!(
+        "cargo:rustc-env=MANIA_CONVERTER_REV={}",
+        mania_converter_rev()
+    );
+    // lang/<语言>/LC_MESSAGES/mania-rating-gui.po中的翻译会打包进程序
+    slint_build::compile_with_config(
+        "ui/main_window.slint",
+        slint_build::CompilerConfiguration::new().with_bundled_translations("lang"),
+    )
+    .unwrap();
     if cfg!(target_os = "windows") {
         let mut res = winresource::WindowsResource::new();
         res.set_icon("ui/icon.ico");
@@ -7,3 +17,29 @@ fn main() {
         res.compile().unwrap();
     }
 }
+
+/// 从Cargo.lock中读取mania-converter的版本和git提交，作为算法版本的一部分，更新后旧的缓存会重新计算
+fn mania_converter_rev() -> String {
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let Some(package) = lock
+        .split("[[package]]")
+        .find(|package| package.contains("name = \"mania-converter\""))
+    else {
+        return "unknown".to_string();
+    };
+    let field = |key: &str| {
+        package
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .map(|value| value.trim_matches('"').to_string())
+    };
+    let version = field("version = ").unwrap_or_default();
+    match field("source = ").and_then(|source| {
+        source
+            .split_once('#')
+            .map(|(_, rev)| rev.chars().take(7).collect::<String>())
+    }) {
+        Some(rev) => format!("{version}@{rev}"),
+        None => version,
+    }
+}
@@ -1,9 +1,47 @@
+mod algorithm;
+mod dedupe;
+mod delta;
+mod details;
+mod discovery;
+mod estimate;
+mod history;
+mod journal;
+mod manifest;
 mod misc;
+mod overall;
+mod progress;
 mod ratings;
+mod report;
+mod session;
+mod status;
 
-pub use misc::get_osu_install_path;
+pub use algorithm::{
+    ALGORITHM_VERSIONS, AlgorithmVersion, algorithm_label, algorithm_stamp, current_algorithm,
+};
+pub(crate) use dedupe::list_plays;
+pub use dedupe::{DedupePolicy, dedupe_ratings, removed_duplicates, split_duplicates};
+pub use delta::{B30Delta, b30_entry_threshold, compute_b30_delta};
+pub use details::{MapDetails, load_map_details, replay_path};
+pub use discovery::{default_dialog_location, get_osu_install_path};
+pub use estimate::{LiveEstimate, estimate_live};
+pub use history::{HistoryRange, history_overall_rating, select_history};
+pub use journal::{
+    IMPORTED_RATINGS, imported_ratings, load_journal, record_session_score, session_dir,
+};
+pub use manifest::{CardCheck, CheckResult, ExportManifest, VerifyReport};
 pub(crate) use misc::{get_db_path, get_replay_timestamp};
-pub use ratings::{calc_mod_rating, prepare_ratings};
+pub use overall::{
+    DEFAULT_SLOTS, MAX_SLOTS, OverallDefinition, OverallRating, overall_definition, overall_rating,
+    table_size,
+};
+pub use progress::{IngestionProgress, IngestionStage, ProgressSnapshot};
+pub use ratings::{
+    SrVariant, calc_sr_rating, extract_new_ratings, merge_ratings, play_sr, player_list_name,
+    prepare_ratings,
+};
+pub use report::{INGESTION_REPORT, IngestionReport, SkipReason, SkippedMap, SkippedScore};
+pub use session::{SessionSummary, summarize_session};
+pub use status::{RankedStatus, beatmap_status, counts_for_b30};
 
 use chrono::{DateTime, Utc};
 use mania_converter::BeatMapInfo;
@@ -14,6 +52,7 @@ use std::path::PathBuf;
 #[derive(Debug, Clone)]
 pub struct BeatmapStoreInfo {
     path: PathBuf,
+    status: RankedStatus,
     plays: Vec<PlayRecord>,
 }
 
@@ -25,6 +64,30 @@ pub struct PlayRecord {
     pub accuracy: f64,
     pub accuracy_rating: f64,
     pub timestamp: DateTime<Utc>,
+    /// 实时模式记录的失败游玩。osu!不保存失败的成绩，只显示在实时模式中，不计入成绩表
+    pub failed: bool,
+}
+
+impl PlayRecord {
+    /// 影响Rating的mod的简称，如"HTV2"
+    pub fn mods_text(&self) -> String {
+        let mut names = Vec::new();
+        for (m, name) in [
+            (osu_db::Mod::Easy, "EZ"),
+            (osu_db::Mod::HalfTime, "HT"),
+            (osu_db::Mod::HardRock, "HR"),
+            (osu_db::Mod::DoubleTime, "DT"),
+            (osu_db::Mod::Nightcore, "NC"),
+        ] {
+            if self.mods.contains(m) {
+                names.push(name);
+            }
+        }
+        if self.mods.bits() & 0x2000_0000 != 0 {
+            names.push("V2");
+        }
+        names.join("")
+    }
 }
 
 impl Default for PlayRecord {
@@ -36,6 +99,7 @@ impl Default for PlayRecord {
             accuracy: 0.0,
             accuracy_rating: 0.0,
             timestamp: DateTime::default(),
+            failed: false,
         }
     }
 }
@@ -45,6 +109,7 @@ pub struct RatingMapInfo {
     pub hash: String,
     pub path: PathBuf,
     pub info: BeatMapInfo,
+    pub status: RankedStatus,
 }
 
 #[derive(Debug, Clone)]
@@ -54,3 +119,20 @@ pub struct RatingInfo {
     pub diff_const: f64,
     pub rating: f64,
 }
+
+impl RatingInfo {
+    /// 同一玩家在同一谱面、同一时间的成绩视为同一个成绩
+    pub fn is_same_score(&self, other: &RatingInfo) -> bool {
+        self.map_info.hash == other.map_info.hash
+            && self.score_info.timestamp == other.score_info.timestamp
+            && self.score_info.player == other.score_info.player
+    }
+
+    /// 同一次游玩在不同来源（实时模式和scores.db）中的时间戳会略有差别，因此比较判定和mod
+    pub fn is_same_play(&self, other: &RatingInfo) -> bool {
+        self.map_info.hash == other.map_info.hash
+            && self.score_info.player == other.score_info.player
+            && self.score_info.mods.bits() == other.score_info.mods.bits()
+            && self.score_info.judgement_num == other.score_info.judgement_num
+    }
+}
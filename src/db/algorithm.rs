@@ -0,0 +1,52 @@
+use std::sync::LazyLock;
+
+use crate::tr;
+
+/// mania_converter的版本和git提交，由build.rs从Cargo.lock中读取
+const MANIA_CONVERTER_REV: &str = env!("MANIA_CONVERTER_REV");
+
+/// 星级算法和Rating表的一个版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlgorithmVersion {
+    /// 写入缓存和导出文件的版本号
+    pub id: &'static str,
+    /// sunnyxxy的Star-Rating-Rebirth版本
+    pub star_rating: &'static str,
+    /// 由定数和Acc计算Rating的分段函数的版本
+    pub rating_table: &'static str,
+}
+
+/// 所有使用过的算法版本，最后一个为当前版本。修改星级或Rating的计算方式时在末尾添加新版本
+pub const ALGORITHM_VERSIONS: &[AlgorithmVersion] = &[AlgorithmVersion {
+    id: "sr20250415-rt1",
+    star_rating: "20250415",
+    rating_table: "1",
+}];
+
+static ALGORITHM_STAMP: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        "{}+mania-converter@{}",
+        current_algorithm().id,
+        MANIA_CONVERTER_REV
+    )
+});
+
+pub fn current_algorithm() -> &'static AlgorithmVersion {
+    ALGORITHM_VERSIONS.last().unwrap()
+}
+
+/// 当前算法的完整版本，包括mania_converter的版本。缓存和导出文件中的版本与之不同时需要重新计算
+pub fn algorithm_stamp() -> &'static str {
+    &ALGORITHM_STAMP
+}
+
+/// 导出图片页脚中显示的算法版本
+pub fn algorithm_label() -> String {
+    let algorithm = current_algorithm();
+    tr!(
+        "Star-Rating-Rebirth {} · Rating表 v{} · mania-converter {}",
+        algorithm.star_rating,
+        algorithm.rating_table,
+        MANIA_CONVERTER_REV
+    )
+}
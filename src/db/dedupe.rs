@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use mania_converter::BeatMapInfo;
+
+use crate::db::RatingInfo;
+use crate::db::ratings::{best_per_hash, player_list_name};
+use crate::settings::SETTINGS;
+use crate::tr;
+
+/// 成绩表的去重方式。无论哪种方式，同一张谱面（hash相同）都只保留最高的成绩
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupePolicy {
+    /// 每张谱面一个成绩
+    #[default]
+    Hash,
+    /// 每个谱面集（beatmap_set_id）一个成绩
+    BeatmapSet,
+    /// 标题和艺术家相同的谱面视为同一首歌，每首歌一个成绩
+    Song,
+}
+
+impl DedupePolicy {
+    pub const ALL: [DedupePolicy; 3] = [
+        DedupePolicy::Hash,
+        DedupePolicy::BeatmapSet,
+        DedupePolicy::Song,
+    ];
+
+    /// 设置中显示的名称
+    pub fn label(self) -> String {
+        match self {
+            DedupePolicy::Hash => tr!("每张谱面一个成绩"),
+            DedupePolicy::BeatmapSet => tr!("每个谱面集一个成绩"),
+            DedupePolicy::Song => tr!("每首歌一个成绩（标题+艺术家）"),
+        }
+    }
+
+    /// 同一个键的成绩只保留一个，取不到谱面集或标题时退回到谱面hash
+    pub(crate) fn key(self, hash: &str, beatmap_info: &BeatMapInfo) -> String {
+        match self {
+            DedupePolicy::BeatmapSet if beatmap_info.beatmap_set_id > 0 => {
+                format!("set:{}", beatmap_info.beatmap_set_id)
+            }
+            DedupePolicy::Song => {
+                let title = normalize(&beatmap_info.title);
+                let artist = normalize(&beatmap_info.artist);
+                if title.is_empty() {
+                    format!("hash:{hash}")
+                } else {
+                    format!("song:{title}|{artist}")
+                }
+            }
+            _ => format!("hash:{hash}"),
+        }
+    }
+}
+
+/// 去掉括号中的内容（如TV Size、Cut Ver.）、空白和标点，并统一为小写
+fn normalize(text: &str) -> String {
+    let mut result = String::new();
+    let mut depth = 0usize;
+    for c in text.chars() {
+        match c {
+            '(' | '[' | '（' | '【' => depth += 1,
+            ')' | ']' | '）' | '】' => depth = depth.saturating_sub(1),
+            _ if depth == 0 && c.is_alphanumeric() => result.extend(c.to_lowercase()),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// 按设置的去重方式整理成绩，返回(保留的成绩, 因为重复被去掉的成绩)，都按Rating从高到低排序
+pub fn split_duplicates(ratings: Vec<RatingInfo>) -> (Vec<RatingInfo>, Vec<RatingInfo>) {
+    let policy = SETTINGS.lock().unwrap().dedupe;
+    let best = best_per_hash(ratings);
+    if policy == DedupePolicy::Hash {
+        return (best, Vec::new());
+    }
+
+    let mut kept: HashMap<String, RatingInfo> = HashMap::new();
+    let mut duplicates = Vec::new();
+    // best已经按Rating排序，每个键第一次出现的成绩就是最高的
+    for info in best {
+        let key = policy.key(&info.map_info.hash, &info.map_info.info);
+        if kept.contains_key(&key) {
+            duplicates.push(info);
+        } else {
+            kept.insert(key, info);
+        }
+    }
+
+    let mut kept = kept.into_values().collect::<Vec<_>>();
+    kept.sort_unstable_by(|a, b| b.rating.partial_cmp(&a.rating).unwrap_or(Ordering::Equal));
+    (kept, duplicates)
+}
+
+/// 按设置的去重方式整理成绩，只返回保留的成绩
+pub fn dedupe_ratings(ratings: Vec<RatingInfo>) -> Vec<RatingInfo> {
+    split_duplicates(ratings).0
+}
+
+/// 某个列表中因为重复被去掉的成绩，显示在备选区。[Recent 30]不去重
+pub fn removed_duplicates(
+    list_name: &str,
+    player_scores: &HashMap<String, Vec<RatingInfo>>,
+) -> Vec<RatingInfo> {
+    if list_name == "[Recent 30]" || SETTINGS.lock().unwrap().dedupe == DedupePolicy::Hash {
+        return Vec::new();
+    }
+    split_duplicates(list_plays(list_name, player_scores)).1
+}
+
+/// 某个列表对应的所有游玩记录（不去重），[All Players]和[Recent 30]包括所有玩家
+pub(crate) fn list_plays(
+    list_name: &str,
+    player_scores: &HashMap<String, Vec<RatingInfo>>,
+) -> Vec<RatingInfo> {
+    // [Recent 30]中保存着所有成绩
+    player_scores
+        .get("[Recent 30]")
+        .into_iter()
+        .flatten()
+        .filter(|info| {
+            list_name == "[All Players]"
+                || list_name == "[Recent 30]"
+                || player_list_name(info.score_info.player.clone()) == list_name
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_ignores_case_whitespace_and_punctuation() {
+        assert_eq!(normalize("Hello, World!"), "helloworld");
+        assert_eq!(normalize("  HELLO   world "), "helloworld");
+        assert_eq!(normalize("Hello-World"), normalize("hello world"));
+    }
+
+    #[test]
+    fn normalize_strips_bracketed_suffixes() {
+        assert_eq!(normalize("Song Title (TV Size)"), "songtitle");
+        assert_eq!(normalize("Song Title [Cut Ver.]"), "songtitle");
+        assert_eq!(normalize("曲名（TV Size）"), "曲名");
+        assert_eq!(normalize("曲名【Extended Mix】"), "曲名");
+        assert_eq!(normalize("A (B (C) D) E"), "ae");
+        assert_eq!(normalize("Unbalanced) Title"), "unbalancedtitle");
+    }
+}
@@ -0,0 +1,74 @@
+use mania_converter::BeatMapInfo;
+
+use crate::db::RatingInfo;
+use crate::db::dedupe::dedupe_ratings;
+use crate::db::overall::{b30_average, overall_definition};
+use crate::settings::SETTINGS;
+
+/// 新成绩与玩家当前B30（总Rating计入的N个成绩）的比较结果
+#[derive(Debug, Clone)]
+pub struct B30Delta {
+    /// 合并后在成绩表中的排名，从1开始。同一谱面已有更高的成绩时为None
+    pub new_rank: Option<usize>,
+    /// 被挤出B30的成绩：按去重方式与新成绩重复、原来在B30中的成绩，或者原来的第N名。
+    /// 新成绩没有进入B30或B30原本不满N个时为None
+    pub replaced: Option<RatingInfo>,
+    /// 计算时总Rating计入的成绩数量N
+    pub slots: usize,
+    pub average_before: f64,
+    pub average_after: f64,
+}
+
+impl B30Delta {
+    pub fn enters_b30(&self) -> bool {
+        self.new_rank.is_some_and(|rank| rank <= self.slots)
+    }
+}
+
+/// 计算新成绩合并进玩家成绩表（已按谱面去重并排序）后B30的变化
+pub fn compute_b30_delta(current: &[RatingInfo], new_score: &RatingInfo) -> B30Delta {
+    let mut merged = current.to_vec();
+    merged.push(new_score.clone());
+    let merged = dedupe_ratings(merged);
+    let slots = overall_definition().slots;
+
+    let new_rank = merged
+        .iter()
+        .position(|info| info.is_same_score(new_score))
+        .map(|i| i + 1);
+    let enters_b30 = new_rank.is_some_and(|rank| rank <= slots);
+
+    let replaced = if enters_b30 {
+        current
+            .iter()
+            .take(slots)
+            .find(|info| !merged.iter().take(slots).any(|m| m.is_same_score(info)))
+            .cloned()
+    } else {
+        None
+    };
+
+    B30Delta {
+        new_rank,
+        replaced,
+        slots,
+        average_before: b30_average(current),
+        average_after: b30_average(&merged),
+    }
+}
+
+/// 新成绩进入B30需要超过的Rating。按设置的去重方式与之重复的成绩已经在B30中时为该成绩的Rating，
+/// 否则为第N名的Rating（不足N个成绩时为0）
+pub fn b30_entry_threshold(current: &[RatingInfo], hash: &str, beatmap_info: &BeatMapInfo) -> f64 {
+    let slots = overall_definition().slots.max(1);
+    let cutoff = current.get(slots - 1).map_or(0.0, |info| info.rating);
+    let policy = SETTINGS.lock().unwrap().dedupe;
+    let key = policy.key(hash, beatmap_info);
+    match current
+        .iter()
+        .position(|info| policy.key(&info.map_info.hash, &info.map_info.info) == key)
+    {
+        Some(i) if i < slots => current[i].rating,
+        _ => cutoff,
+    }
+}
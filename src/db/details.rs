@@ -0,0 +1,50 @@
+use mania_converter::osu_func::{OsuDataV128, calculate_from_data};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::db::RatingInfo;
+use crate::db::misc::get_replay_file_name;
+use crate::db::ratings::SrVariant;
+
+/// 详情窗口中显示的谱面信息，需要重新读取谱面文件
+#[derive(Debug, Clone)]
+pub struct MapDetails {
+    pub od: f64,
+    pub hp: f64,
+    /// 每个速率和判定mod下的星级
+    pub srs: Vec<(SrVariant, f64)>,
+}
+
+/// 读取谱面文件，计算所有mod组合的星级
+pub fn load_map_details(path: &Path) -> io::Result<MapDetails> {
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid path"))?;
+    let osu_data = OsuDataV128::from_file(path_str)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?
+        .to_legacy();
+    let srs = SrVariant::ALL
+        .into_iter()
+        .map(|variant| {
+            let mut data = osu_data.clone();
+            if let Some(od) = variant.adjusted_od(data.misc.od as f64) {
+                data.misc.od = od as _;
+            }
+            let sr = calculate_from_data(&data, variant.rate()).unwrap_or(0.0);
+            (variant, sr)
+        })
+        .collect();
+    Ok(MapDetails {
+        od: osu_data.misc.od as f64,
+        hp: osu_data.misc.hp as f64,
+        srs,
+    })
+}
+
+/// 成绩对应的回放文件，osu!只会为本地保存了回放的成绩生成该文件
+pub fn replay_path(osu_dir: &Path, info: &RatingInfo) -> PathBuf {
+    osu_dir.join("Data").join("r").join(get_replay_file_name(
+        info.score_info.timestamp,
+        &info.map_info.hash,
+    ))
+}
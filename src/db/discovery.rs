@@ -0,0 +1,140 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// 查找osu!stable安装目录，优先级：`OSU_DIR`环境变量 > Windows注册表 > 常见的Wine/Proton目录
+pub fn get_osu_install_path() -> Option<PathBuf> {
+    from_env_override()
+        .or_else(from_registry)
+        .or_else(from_common_layouts)
+}
+
+/// 判断一个文件夹是否像osu!stable的安装目录
+pub fn is_osu_dir(path: &Path) -> bool {
+    path.join("osu!.exe").is_file()
+        || (path.join("osu!.db").is_file() && path.join("scores.db").is_file())
+}
+
+/// 文件选择对话框的起始位置，找不到桌面时退回到用户主目录
+pub fn default_dialog_location() -> PathBuf {
+    let home = home_dir().unwrap_or_default();
+    let desktop = home.join("Desktop");
+    if desktop.is_dir() { desktop } else { home }
+}
+
+fn from_env_override() -> Option<PathBuf> {
+    env::var_os("OSU_DIR")
+        .map(PathBuf::from)
+        .filter(|p| is_osu_dir(p))
+}
+
+#[cfg(target_os = "windows")]
+fn from_registry() -> Option<PathBuf> {
+    use winreg::RegKey;
+    use winreg::enums::*;
+
+    let try_registry = |key: *mut core::ffi::c_void, path: &str| -> Option<PathBuf> {
+        RegKey::predef(key)
+            .open_subkey(path)
+            .ok()?
+            .get_value::<String, &str>("")
+            .ok()?
+            .split('\"')
+            .nth(1)
+            .and_then(|exe_path| PathBuf::from(exe_path).parent().map(|p| p.to_path_buf()))
+            .filter(|p| p.is_dir())
+    };
+
+    try_registry(HKEY_CLASSES_ROOT, r"osustable.File.osz\Shell\Open\Command").or_else(|| {
+        try_registry(
+            HKEY_CURRENT_USER,
+            r"Software\Classes\osustable.File.osz\Shell\Open\Command",
+        )
+    })
+}
+
+#[cfg(not(target_os = "windows"))]
+fn from_registry() -> Option<PathBuf> {
+    None
+}
+
+#[cfg(target_os = "windows")]
+fn from_common_layouts() -> Option<PathBuf> {
+    // 没有写注册表的便携版一般在 %LOCALAPPDATA%\osu!
+    env::var_os("LOCALAPPDATA")
+        .map(|p| PathBuf::from(p).join("osu!"))
+        .filter(|p| is_osu_dir(p))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn from_common_layouts() -> Option<PathBuf> {
+    let home = home_dir()?;
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".local/share"));
+
+    // osu-winello把游戏目录记录在osuconfig/osupath中，默认安装在 ~/.local/share/osu-wine/osu!
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    if let Ok(p) = std::fs::read_to_string(data_home.join("osuconfig/osupath")) {
+        candidates.push(PathBuf::from(p.trim()));
+    }
+    candidates.push(data_home.join("osu-wine/osu!"));
+
+    // 各种Wine前缀：WINEPREFIX, 默认的~/.wine, osu-winello, Lutris, umu, Steam/Proton
+    let mut prefixes: Vec<PathBuf> = Vec::new();
+    if let Some(p) = env::var_os("WINEPREFIX") {
+        prefixes.push(PathBuf::from(p));
+    }
+    prefixes.push(home.join(".wine"));
+    prefixes.push(home.join(".wine-osu"));
+    prefixes.push(data_home.join("wineprefixes/osu-wineprefix"));
+    for games_dir in [home.join("Games"), home.join("Games/umu")] {
+        prefixes.extend(sub_dirs(&games_dir));
+    }
+    for steam_dir in [
+        home.join(".steam/steam"),
+        data_home.join("Steam"),
+        home.join(".var/app/com.valvesoftware.Steam/data/Steam"),
+    ] {
+        prefixes.extend(
+            sub_dirs(&steam_dir.join("steamapps/compatdata"))
+                .into_iter()
+                .map(|p| p.join("pfx")),
+        );
+    }
+
+    for prefix in prefixes {
+        let drive_c = prefix.join("drive_c");
+        if !drive_c.is_dir() {
+            continue;
+        }
+        for user_dir in sub_dirs(&drive_c.join("users")) {
+            candidates.push(user_dir.join("AppData/Local/osu!"));
+            candidates.push(user_dir.join("Local Settings/Application Data/osu!"));
+        }
+        candidates.push(drive_c.join("osu!"));
+        candidates.push(drive_c.join("Program Files/osu!"));
+        candidates.push(drive_c.join("Program Files (x86)/osu!"));
+    }
+
+    candidates.into_iter().find(|p| is_osu_dir(p))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn sub_dirs(dir: &Path) -> Vec<PathBuf> {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    let home = env::var_os("USERPROFILE");
+    #[cfg(not(target_os = "windows"))]
+    let home = env::var_os("HOME");
+    home.map(PathBuf::from)
+}
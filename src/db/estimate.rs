@@ -0,0 +1,90 @@
+use crate::db::calc_sr_rating;
+use crate::db::ratings::calc_rating;
+
+/// 游玩中根据当前判定估算的成绩
+#[derive(Debug, Clone)]
+pub struct LiveEstimate {
+    pub accuracy_rating: f64,
+    pub diff_const: f64,
+    pub rating: f64,
+    /// 进入B30（前N名）需要超过的Rating
+    pub b30_threshold: f64,
+    /// 剩余判定需要达到的Acc，0表示已经确保进入B30。
+    /// None表示剩余全部Marvelous也无法进入B30
+    pub needed_accuracy: Option<f64>,
+}
+
+fn judgement_weight(hits: &[u32; 6]) -> f64 {
+    let [marv, perf, great, good, bad, _] = *hits;
+    310.0 * marv as f64
+        + 300.0 * perf as f64
+        + 200.0 * great as f64
+        + 100.0 * good as f64
+        + 50.0 * bad as f64
+}
+
+/// 由判定计算Rating Acc，没有判定时为100%
+pub(crate) fn judgement_accuracy(hits: &[u32; 6]) -> f64 {
+    let judged = hits.iter().sum::<u32>();
+    if judged == 0 {
+        100.0
+    } else {
+        judgement_weight(hits) / (3.1 * judged as f64)
+    }
+}
+
+/// 达到目标Rating需要的最低Acc，100%也达不到时返回None
+fn required_accuracy(diff_const: f64, target: f64) -> Option<f64> {
+    if calc_rating(diff_const, 100.0) < target {
+        return None;
+    }
+    if target <= 0.0 {
+        return Some(0.0);
+    }
+    // Rating随Acc单调不减，二分查找
+    let (mut low, mut high) = (0.0, 100.0);
+    for _ in 0..50 {
+        let mid = (low + high) / 2.0;
+        if calc_rating(diff_const, mid) >= target {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    Some(high)
+}
+
+/// 由当前判定估算Rating，以及剩余的判定需要多少Acc才能进入B30。
+/// `sr`是已经考虑了mod的星级，`total_judgements`是整张谱面的判定数量
+pub fn estimate_live(
+    sr: f64,
+    hits: [u32; 6],
+    total_judgements: u32,
+    b30_threshold: f64,
+) -> LiveEstimate {
+    let judged = hits.iter().sum::<u32>();
+    let weight = judgement_weight(&hits);
+    let accuracy_rating = judgement_accuracy(&hits);
+    let (diff_const, rating) = calc_sr_rating(sr, accuracy_rating);
+
+    let remaining = total_judgements.saturating_sub(judged);
+    let needed_accuracy = required_accuracy(diff_const, b30_threshold).and_then(|target| {
+        let total = judged + remaining;
+        let needed_weight = target * 3.1 * total as f64 - weight;
+        if needed_weight <= 0.0 {
+            Some(0.0)
+        } else if remaining == 0 {
+            None
+        } else {
+            Some(needed_weight / (3.1 * remaining as f64)).filter(|acc| *acc <= 100.0)
+        }
+    });
+
+    LiveEstimate {
+        accuracy_rating,
+        diff_const,
+        rating,
+        b30_threshold,
+        needed_accuracy,
+    }
+}
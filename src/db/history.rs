@@ -0,0 +1,72 @@
+use chrono::{Local, NaiveDate};
+use std::collections::HashMap;
+
+use crate::db::RatingInfo;
+use crate::db::dedupe::{dedupe_ratings, list_plays};
+use crate::db::overall::overall_rating;
+use crate::tr;
+
+/// 分页导出的成绩范围
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HistoryRange {
+    /// 成绩表中最好的N个成绩
+    Top(usize),
+    /// 日期范围内（包括两端，按本地时间）的所有游玩记录，None表示不限制
+    Dates {
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    },
+}
+
+impl HistoryRange {
+    /// 导出页面上显示的范围说明
+    pub fn title(&self) -> String {
+        match self {
+            HistoryRange::Top(count) => tr!("最好的{}个成绩", count),
+            HistoryRange::Dates { from, to } => {
+                let format_date = |date: &Option<NaiveDate>| {
+                    date.map_or_else(|| "...".to_string(), |date| date.to_string())
+                };
+                tr!("{} ~ {}的所有成绩", format_date(from), format_date(to))
+            }
+        }
+    }
+}
+
+/// 按范围选出要导出的成绩。最好的N个成绩按Rating排序，日期范围内的成绩从新到旧排序
+pub fn select_history(
+    list_name: &str,
+    player_scores: &HashMap<String, Vec<RatingInfo>>,
+    range: &HistoryRange,
+) -> Vec<RatingInfo> {
+    match range {
+        HistoryRange::Top(count) => player_scores
+            .get(list_name)
+            .map(|ratings| ratings.iter().take(*count).cloned().collect())
+            .unwrap_or_default(),
+        HistoryRange::Dates { from, to } => {
+            let mut plays = list_plays(list_name, player_scores)
+                .into_iter()
+                .filter(|info| {
+                    let date = info.score_info.timestamp.with_timezone(&Local).date_naive();
+                    from.is_none_or(|from| date >= from) && to.is_none_or(|to| date <= to)
+                })
+                .collect::<Vec<_>>();
+            plays.sort_unstable_by(|a, b| b.score_info.timestamp.cmp(&a.score_info.timestamp));
+            plays
+        }
+    }
+}
+
+/// 导出页面标题中的总Rating。日期范围内可能有同一谱面的多次游玩，
+/// 所以不按导出的成绩计算，而是使用玩家成绩表按设置去重后的总Rating，与主界面一致
+pub fn history_overall_rating(
+    list_name: &str,
+    player_scores: &HashMap<String, Vec<RatingInfo>>,
+) -> f64 {
+    let ratings = player_scores.get(list_name).cloned().unwrap_or_default();
+    if list_name == "[Recent 30]" {
+        return overall_rating(&ratings);
+    }
+    overall_rating(&dedupe_ratings(ratings))
+}
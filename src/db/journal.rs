@@ -0,0 +1,171 @@
+use chrono::{DateTime, Local, Utc};
+use mania_converter::osu_func::OsuDataV128;
+use osu_db::ModSet;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+use crate::db::ratings::rate_play;
+use crate::db::{PlayRecord, RatingInfo, RatingMapInfo, algorithm_stamp, beatmap_status};
+
+const SESSION_DIR: &str = "sessions";
+
+/// 实时模式记录的单个成绩，每行一个JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub player: String,
+    pub beatmap_hash: String,
+    pub beatmap_path: PathBuf,
+    pub mods: u32,
+    pub judgement_num: [u32; 6],
+    pub accuracy: f64,
+    pub accuracy_rating: f64,
+    pub timestamp: DateTime<Utc>,
+    pub diff_const: f64,
+    pub rating: f64,
+    /// 计算Rating时的算法版本，旧的记录中没有这一项
+    #[serde(default)]
+    pub algorithm: String,
+    /// 失败的游玩，载入时只显示在实时模式中，不合并进成绩表
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub failed: bool,
+}
+
+impl From<&RatingInfo> for JournalEntry {
+    fn from(info: &RatingInfo) -> Self {
+        JournalEntry {
+            player: info.score_info.player.clone(),
+            beatmap_hash: info.map_info.hash.clone(),
+            beatmap_path: info.map_info.path.clone(),
+            mods: info.score_info.mods.bits(),
+            judgement_num: info.score_info.judgement_num,
+            accuracy: info.score_info.accuracy,
+            accuracy_rating: info.score_info.accuracy_rating,
+            timestamp: info.score_info.timestamp,
+            diff_const: info.diff_const,
+            rating: info.rating,
+            algorithm: algorithm_stamp().to_string(),
+            failed: info.score_info.failed,
+        }
+    }
+}
+
+impl JournalEntry {
+    /// 重新读取谱面文件以恢复谱面信息，谱面文件不存在时返回None。
+    /// 记录中的算法版本与当前不同时，按当前的算法重新计算Rating
+    pub fn to_rating_info(&self) -> Option<RatingInfo> {
+        let mods = ModSet::from_bits(self.mods);
+        let (info, diff_const, rating) = if self.algorithm == algorithm_stamp() {
+            let info = OsuDataV128::from_file(self.beatmap_path.to_str()?)
+                .ok()?
+                .to_legacy()
+                .to_beatmap_info(true);
+            (info, self.diff_const, self.rating)
+        } else {
+            rate_play(
+                &self.beatmap_hash,
+                &self.beatmap_path,
+                mods,
+                self.accuracy_rating,
+            )?
+        };
+        Some(RatingInfo {
+            map_info: RatingMapInfo {
+                hash: self.beatmap_hash.clone(),
+                path: self.beatmap_path.clone(),
+                info,
+                status: beatmap_status(&self.beatmap_hash),
+            },
+            score_info: PlayRecord {
+                player: self.player.clone(),
+                mods,
+                judgement_num: self.judgement_num,
+                accuracy: self.accuracy,
+                accuracy_rating: self.accuracy_rating,
+                timestamp: self.timestamp,
+                failed: self.failed,
+            },
+            diff_const,
+            rating,
+        })
+    }
+}
+
+/// 一次程序运行对应一个记录文件，在第一次记录成绩时创建
+pub struct SessionJournal {
+    path: PathBuf,
+    file: File,
+}
+
+impl SessionJournal {
+    pub fn create() -> io::Result<Self> {
+        let dir = session_dir()?;
+        fs::create_dir_all(&dir)?;
+        let file_name = format!("session-{}.jsonl", Local::now().format("%Y%m%d-%H%M%S"));
+        let path = dir.join(file_name);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(SessionJournal { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&mut self, info: &RatingInfo) -> io::Result<()> {
+        let line = serde_json::to_string(&JournalEntry::from(info))?;
+        writeln!(self.file, "{line}")?;
+        self.file.flush()
+    }
+}
+
+static SESSION_JOURNAL: LazyLock<Mutex<Option<SessionJournal>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+/// 从记录文件载入、合并进成绩表的成绩。重新读取osu!文件夹时需要再次合并。
+/// 持有SCORES_DATA的锁时不要获取这个锁，以免与载入记录互相等待
+pub static IMPORTED_RATINGS: LazyLock<Mutex<Vec<RatingInfo>>> =
+    LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// 已载入成绩的副本，用于合并进新读取的成绩表
+pub fn imported_ratings() -> Vec<RatingInfo> {
+    IMPORTED_RATINGS.lock().unwrap().clone()
+}
+
+/// 将实时模式的成绩追加到本次运行的记录文件中
+pub fn record_session_score(info: &RatingInfo) -> io::Result<PathBuf> {
+    let mut guard = SESSION_JOURNAL.lock().unwrap();
+    let journal = match &mut *guard {
+        Some(journal) => journal,
+        slot => slot.insert(SessionJournal::create()?),
+    };
+    journal.append(info)?;
+    Ok(journal.path().to_path_buf())
+}
+
+/// 读取记录文件，返回能够恢复的成绩和跳过的行数（格式错误或谱面文件已不存在）
+pub fn load_journal(path: &Path) -> io::Result<(Vec<RatingInfo>, usize)> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut ratings = Vec::new();
+    let mut skipped = 0;
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<JournalEntry>(&line)
+            .ok()
+            .and_then(|entry| entry.to_rating_info())
+        {
+            Some(info) => ratings.push(info),
+            None => skipped += 1,
+        }
+    }
+    Ok((ratings, skipped))
+}
+
+pub fn session_dir() -> io::Result<PathBuf> {
+    Ok(env::current_dir()?.join(SESSION_DIR))
+}
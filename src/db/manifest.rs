@@ -0,0 +1,452 @@
+use chrono::{DateTime, Local, Utc};
+use osu_db::ModSet;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::db::algorithm::algorithm_stamp;
+use crate::db::estimate::judgement_accuracy;
+use crate::db::ratings::rate_play;
+use crate::db::{PlayRecord, RatingInfo};
+use crate::tr;
+
+/// 清单格式的版本，格式改变时增加
+const MANIFEST_FORMAT: u32 = 1;
+/// 验证时允许的Rating和Acc误差
+const TOLERANCE: f64 = 0.005;
+
+/// 嵌入导出图片和PDF的成绩清单。
+/// `content_hash`是其余字段不带密钥的SHA-256，任何人都能重新计算，只能发现意外损坏或不完整的修改。
+/// 验证时每个成绩的Rating都由清单中的判定和Mods重新计算，并与本地的游玩记录核对；
+/// 两者只能说明清单与本地的谱面和记录一致，不能证明其他电脑上的成绩真实
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportManifest {
+    #[serde(flatten)]
+    pub body: ManifestBody,
+    pub content_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestBody {
+    pub format: u32,
+    pub app_version: String,
+    pub algorithm: String,
+    pub generated_at: DateTime<Utc>,
+    pub player: String,
+    pub cards: Vec<ManifestCard>,
+}
+
+/// 一张卡片对应的成绩
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestCard {
+    /// 卡片上显示的排名
+    pub rank: usize,
+    pub player: String,
+    pub map_hash: String,
+    pub mods: u32,
+    pub judgements: [u32; 6],
+    pub accuracy: f64,
+    pub accuracy_rating: f64,
+    pub timestamp: DateTime<Utc>,
+    pub diff_const: f64,
+    pub rating: f64,
+}
+
+impl ExportManifest {
+    /// 由导出的卡片生成清单，`cards`中的序号为卡片上显示的排名减1
+    pub fn new<'a>(player: &str, cards: impl IntoIterator<Item = (usize, &'a RatingInfo)>) -> Self {
+        let body = ManifestBody {
+            format: MANIFEST_FORMAT,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            algorithm: algorithm_stamp().to_string(),
+            generated_at: Utc::now(),
+            player: player.to_string(),
+            cards: cards
+                .into_iter()
+                .map(|(i, info)| ManifestCard {
+                    rank: i + 1,
+                    player: info.score_info.player.clone(),
+                    map_hash: info.map_info.hash.clone(),
+                    mods: info.score_info.mods.bits(),
+                    judgements: info.score_info.judgement_num,
+                    accuracy: info.score_info.accuracy,
+                    accuracy_rating: info.score_info.accuracy_rating,
+                    timestamp: info.score_info.timestamp,
+                    diff_const: info.diff_const,
+                    rating: info.rating,
+                })
+                .collect(),
+        };
+        let content_hash = body.content_hash();
+        ExportManifest { body, content_hash }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// 用本地的成绩重新核对清单，`plays`为本地的所有游玩记录。
+    /// 每张卡片都用清单中的判定和Mods按本地的谱面重新计算，需要读取谱面，应在后台线程调用
+    pub fn verify(&self, plays: &[RatingInfo]) -> VerifyReport {
+        let cards = self
+            .body
+            .cards
+            .iter()
+            .map(|card| {
+                let local = plays
+                    .iter()
+                    .find(|info| {
+                        info.map_info.hash == card.map_hash
+                            && info.score_info.player == card.player
+                            && info.score_info.timestamp == card.timestamp
+                    })
+                    // 实时模式记录的时间戳与scores.db中略有差别
+                    .or_else(|| {
+                        plays.iter().find(|info| {
+                            info.map_info.hash == card.map_hash
+                                && info.score_info.player == card.player
+                                && info.score_info.mods.bits() == card.mods
+                                && info.score_info.judgement_num == card.judgements
+                        })
+                    });
+                // 本地没有这个成绩时，也可以用同一张谱面的其他记录找到谱面文件
+                let map = local.or_else(|| {
+                    plays
+                        .iter()
+                        .find(|info| info.map_info.hash == card.map_hash)
+                });
+                let mut mismatches = match map {
+                    Some(map) => card.recompute_mismatches(&map.map_info.path),
+                    None => vec![tr!("本地没有这张谱面，无法重新计算")],
+                };
+                match local {
+                    Some(info) => {
+                        mismatches.extend(card.mismatches(&info.score_info, info.rating));
+                        CardCheck {
+                            rank: card.rank,
+                            label: format!(
+                                "{} [{}]",
+                                info.map_info.info.title, info.map_info.info.version
+                            ),
+                            result: CheckResult::Checked(mismatches),
+                        }
+                    }
+                    None => CardCheck {
+                        rank: card.rank,
+                        label: map.map_or_else(
+                            || card.map_hash.clone(),
+                            |info| {
+                                format!(
+                                    "{} [{}]",
+                                    info.map_info.info.title, info.map_info.info.version
+                                )
+                            },
+                        ),
+                        result: CheckResult::NotFound(mismatches),
+                    },
+                }
+            })
+            .collect();
+        VerifyReport {
+            player: self.body.player.clone(),
+            generated_at: self.body.generated_at,
+            algorithm: self.body.algorithm.clone(),
+            hash_valid: self.body.content_hash() == self.content_hash,
+            cards,
+        }
+    }
+}
+
+impl ManifestBody {
+    /// 对字段的文本形式计算hash。小数只取4位，重新解析JSON产生的误差不影响结果
+    fn content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!(
+            "{}|{}|{}|{}|{}\n",
+            self.format,
+            self.app_version,
+            self.algorithm,
+            self.generated_at.to_rfc3339(),
+            self.player
+        ));
+        for card in &self.cards {
+            hasher.update(format!(
+                "{}|{}|{}|{}|{:?}|{:.4}|{:.4}|{}|{:.4}|{:.4}\n",
+                card.rank,
+                card.player,
+                card.map_hash,
+                card.mods,
+                card.judgements,
+                card.accuracy,
+                card.accuracy_rating,
+                card.timestamp.to_rfc3339(),
+                card.diff_const,
+                card.rating
+            ));
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+impl ManifestCard {
+    /// 由清单中的判定和Mods按本地谱面重新计算，与清单中记录的数值不一致的字段
+    fn recompute_mismatches(&self, path: &Path) -> Vec<String> {
+        let accuracy_rating = judgement_accuracy(&self.judgements);
+        let Some((_, diff_const, rating)) = rate_play(
+            &self.map_hash,
+            path,
+            ModSet::from_bits(self.mods),
+            accuracy_rating,
+        ) else {
+            return vec![tr!("无法读取谱面，无法重新计算")];
+        };
+        let mut mismatches = Vec::new();
+        if (self.accuracy_rating - accuracy_rating).abs() > TOLERANCE {
+            mismatches.push(tr!(
+                "Acc {}% ≠ 按判定计算 {}%",
+                format!("{:.02}", self.accuracy_rating),
+                format!("{accuracy_rating:.02}")
+            ));
+        }
+        if (self.diff_const - diff_const).abs() > TOLERANCE {
+            mismatches.push(tr!(
+                "定数 {} ≠ 重新计算 {}",
+                format!("{:.02}", self.diff_const),
+                format!("{diff_const:.02}")
+            ));
+        }
+        if (self.rating - rating).abs() > TOLERANCE {
+            mismatches.push(tr!(
+                "Rating {} ≠ 重新计算 {}",
+                format!("{:.02}", self.rating),
+                format!("{rating:.02}")
+            ));
+        }
+        mismatches
+    }
+
+    /// 与本地成绩不一致的字段，`rating`为本地成绩的Rating
+    fn mismatches(&self, score: &PlayRecord, rating: f64) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if self.judgements != score.judgement_num {
+            mismatches.push(tr!(
+                "判定 {} ≠ 本地 {}",
+                format!("{:?}", self.judgements),
+                format!("{:?}", score.judgement_num)
+            ));
+        }
+        if self.mods != score.mods.bits() {
+            mismatches.push(tr!("Mods {} ≠ 本地 {}", self.mods, score.mods.bits()));
+        }
+        if (self.accuracy_rating - score.accuracy_rating).abs() > TOLERANCE {
+            mismatches.push(tr!(
+                "Acc {}% ≠ 本地 {}%",
+                format!("{:.02}", self.accuracy_rating),
+                format!("{:.02}", score.accuracy_rating)
+            ));
+        }
+        if (self.rating - rating).abs() > TOLERANCE {
+            mismatches.push(tr!(
+                "Rating {} ≠ 本地 {}",
+                format!("{:.02}", self.rating),
+                format!("{rating:.02}")
+            ));
+        }
+        mismatches
+    }
+}
+
+/// 验证导出文件的结果
+#[derive(Debug, Clone)]
+pub struct VerifyReport {
+    pub player: String,
+    pub generated_at: DateTime<Utc>,
+    pub algorithm: String,
+    /// 清单内容与其中的hash一致
+    pub hash_valid: bool,
+    pub cards: Vec<CardCheck>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CardCheck {
+    pub rank: usize,
+    /// 谱面名称，本地没有该成绩时为谱面hash
+    pub label: String,
+    pub result: CheckResult,
+}
+
+#[derive(Debug, Clone)]
+pub enum CheckResult {
+    /// 不一致的字段，为空表示一致
+    Checked(Vec<String>),
+    /// 本地没有这个成绩，附带重新计算时不一致的字段
+    NotFound(Vec<String>),
+}
+
+impl VerifyReport {
+    /// 有问题的卡片数量
+    pub fn problem_count(&self) -> usize {
+        self.cards
+            .iter()
+            .filter(|card| !matches!(&card.result, CheckResult::Checked(m) if m.is_empty()))
+            .count()
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = tr!(
+            "{}，生成于{}：{}张卡片，{}张与本地记录不一致或找不到",
+            self.player,
+            self.generated_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+            self.cards.len(),
+            self.problem_count()
+        );
+        if !self.hash_valid {
+            summary.push_str(&tr!("；清单的hash不匹配，内容可能被修改过"));
+        }
+        if self.algorithm != algorithm_stamp() {
+            summary.push_str(&tr!(
+                "；导出时的算法版本为{}，本地为{}",
+                self.algorithm,
+                algorithm_stamp()
+            ));
+        }
+        summary
+    }
+
+    pub fn item_lines(&self) -> Vec<String> {
+        self.cards
+            .iter()
+            .map(|card| match &card.result {
+                CheckResult::Checked(mismatches) if mismatches.is_empty() => {
+                    tr!("#{} {} - 一致", card.rank, card.label)
+                }
+                CheckResult::Checked(mismatches) => {
+                    tr!("#{} {} - {}", card.rank, card.label, mismatches.join("; "))
+                }
+                CheckResult::NotFound(mismatches) if mismatches.is_empty() => {
+                    tr!("#{} {} - 本地没有这个成绩", card.rank, card.label)
+                }
+                CheckResult::NotFound(mismatches) => tr!(
+                    "#{} {} - 本地没有这个成绩; {}",
+                    card.rank,
+                    card.label,
+                    mismatches.join("; ")
+                ),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(rank: usize) -> ManifestCard {
+        ManifestCard {
+            rank,
+            player: "tester".into(),
+            map_hash: format!("{rank:032x}"),
+            mods: 0,
+            judgements: [900, 80, 15, 3, 1, 1],
+            accuracy: 97.5,
+            accuracy_rating: 96.25,
+            timestamp: DateTime::from_timestamp(1_750_000_000 + rank as i64, 0).unwrap(),
+            diff_const: 12.3456,
+            rating: 11.2345,
+        }
+    }
+
+    fn manifest() -> ExportManifest {
+        let body = ManifestBody {
+            format: MANIFEST_FORMAT,
+            app_version: "0.2.0".into(),
+            algorithm: algorithm_stamp().to_string(),
+            generated_at: DateTime::from_timestamp(1_760_000_000, 0).unwrap(),
+            player: "tester".into(),
+            cards: vec![card(1), card(2)],
+        };
+        let content_hash = body.content_hash();
+        ExportManifest { body, content_hash }
+    }
+
+    fn local_play(card: &ManifestCard) -> PlayRecord {
+        PlayRecord {
+            player: card.player.clone(),
+            mods: ModSet::from_bits(card.mods),
+            judgement_num: card.judgements,
+            accuracy: card.accuracy,
+            accuracy_rating: card.accuracy_rating,
+            timestamp: card.timestamp,
+            failed: false,
+        }
+    }
+
+    #[test]
+    fn json_round_trip_keeps_hash_valid() {
+        let manifest = manifest();
+        let parsed = ExportManifest::from_json(&manifest.to_json()).unwrap();
+        assert_eq!(parsed.content_hash, manifest.content_hash);
+        assert_eq!(parsed.body.content_hash(), manifest.content_hash);
+    }
+
+    #[test]
+    fn editing_any_card_field_changes_hash() {
+        let edits: [fn(&mut ManifestCard); 10] = [
+            |c| c.rank += 1,
+            |c| c.player.push('x'),
+            |c| c.map_hash.replace_range(0..1, "f"),
+            |c| c.mods ^= 1 << 8,
+            |c| c.judgements[0] -= 1,
+            |c| c.accuracy += 0.01,
+            |c| c.accuracy_rating += 0.01,
+            |c| c.timestamp += chrono::Duration::seconds(1),
+            |c| c.diff_const += 0.01,
+            |c| c.rating += 0.01,
+        ];
+        let original = manifest();
+        for (i, edit) in edits.iter().enumerate() {
+            for index in 0..original.body.cards.len() {
+                let mut body = original.body.clone();
+                edit(&mut body.cards[index]);
+                assert_ne!(
+                    body.content_hash(),
+                    original.content_hash,
+                    "edit {i} on card {index} kept the hash"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn verify_reports_edited_manifest() {
+        let mut manifest = manifest();
+        assert!(manifest.verify(&[]).hash_valid);
+        manifest.body.cards[1].judgements[2] += 1;
+        let report = manifest.verify(&[]);
+        assert!(!report.hash_valid);
+        assert_eq!(report.problem_count(), 2);
+    }
+
+    #[test]
+    fn changed_judgement_is_a_mismatch() {
+        let card = card(1);
+        let play = local_play(&card);
+        assert!(card.mismatches(&play, card.rating).is_empty());
+
+        let mut edited = card.clone();
+        edited.judgements[0] += 1;
+        edited.judgements[5] -= 1;
+        let mismatches = edited.mismatches(&play, card.rating);
+        assert_eq!(mismatches.len(), 1);
+        assert!(mismatches[0].contains(&format!("{:?}", edited.judgements)));
+
+        edited.rating += 1.0;
+        assert_eq!(edited.mismatches(&play, card.rating).len(), 2);
+    }
+}
@@ -1,28 +1,7 @@
 use chrono::{DateTime, TimeZone, Utc};
 use std::path::PathBuf;
-use winreg::RegKey;
-use winreg::enums::*;
 
-pub fn get_osu_install_path() -> Option<PathBuf> {
-    let try_registry = |key: *mut core::ffi::c_void, path: &str| -> Option<PathBuf> {
-        RegKey::predef(key)
-            .open_subkey(path)
-            .ok()?
-            .get_value::<String, &str>("")
-            .ok()?
-            .split('\"')
-            .nth(1)
-            .and_then(|exe_path| PathBuf::from(exe_path).parent().map(|p| p.to_path_buf()))
-            .filter(|p| p.is_dir())
-    };
-
-    try_registry(HKEY_CLASSES_ROOT, r"osustable.File.osz\Shell\Open\Command").or_else(|| {
-        try_registry(
-            HKEY_CURRENT_USER,
-            r"Software\Classes\osustable.File.osz\Shell\Open\Command",
-        )
-    })
-}
+use crate::db::get_osu_install_path;
 
 pub(crate) fn get_db_path(osu_exe_dir: &str, db: &str) -> Option<PathBuf> {
     let osu_exe_dir = if osu_exe_dir.is_empty() {
@@ -39,7 +18,6 @@ pub(crate) fn get_db_path(osu_exe_dir: &str, db: &str) -> Option<PathBuf> {
     }
 }
 
-#[allow(dead_code)]
 pub(crate) fn get_replay_file_name(timestamp: DateTime<Utc>, hash: &str) -> String {
     let delta_u64 = get_replay_timestamp(timestamp);
     format!("{hash}-{delta_u64}.osr")
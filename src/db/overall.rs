@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+
+use crate::db::RatingInfo;
+use crate::settings::SETTINGS;
+use crate::tr;
+
+/// 默认计入总Rating的成绩数量
+pub const DEFAULT_SLOTS: usize = 30;
+/// 设置中允许的最大成绩数量
+pub const MAX_SLOTS: usize = 100;
+/// 加权方式中相邻名次的权重比
+const WEIGHT_DECAY: f64 = 0.95;
+
+/// 总Rating的计算方式。都只取Rating最高的N个成绩，N由`OverallDefinition`给出
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverallRating {
+    /// 平均值，不足N个成绩时除以实际数量
+    #[default]
+    Average,
+    /// 总和除以N，空位按0计算
+    SumOverSlots,
+    /// 第n名的权重为0.95^(n-1)，除以N个位置的权重之和，空位按0计算
+    Weighted,
+}
+
+/// 总Rating的定义：计算方式和计入的成绩数量
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverallDefinition {
+    #[serde(rename = "overall")]
+    pub mode: OverallRating,
+    pub slots: usize,
+}
+
+impl OverallRating {
+    pub const ALL: [OverallRating; 3] = [
+        OverallRating::Average,
+        OverallRating::SumOverSlots,
+        OverallRating::Weighted,
+    ];
+
+    /// 设置中显示的名称
+    pub fn label(self) -> String {
+        match self {
+            OverallRating::Average => tr!("平均值（不足N个成绩时按实际数量）"),
+            OverallRating::SumOverSlots => tr!("总和÷N（空位计为0）"),
+            OverallRating::Weighted => tr!("加权（第n名权重0.95^(n-1)）"),
+        }
+    }
+}
+
+impl OverallDefinition {
+    /// 界面和导出图片中显示的简称
+    pub fn short_label(self) -> String {
+        match self.mode {
+            OverallRating::Average => tr!("B{}平均Rating", self.slots),
+            OverallRating::SumOverSlots => tr!("B{} Rating", self.slots),
+            OverallRating::Weighted => tr!("B{}加权Rating", self.slots),
+        }
+    }
+
+    /// 按这个定义计算总Rating，传入的Rating不需要排序
+    pub fn compute(self, ratings: impl IntoIterator<Item = f64>) -> f64 {
+        let slots = self.slots.max(1);
+        let mut ratings = ratings.into_iter().collect::<Vec<_>>();
+        ratings.sort_unstable_by(|a, b| b.total_cmp(a));
+        ratings.truncate(slots);
+        if ratings.is_empty() {
+            return 0.0;
+        }
+        match self.mode {
+            OverallRating::Average => ratings.iter().sum::<f64>() / ratings.len() as f64,
+            OverallRating::SumOverSlots => ratings.iter().sum::<f64>() / slots as f64,
+            OverallRating::Weighted => {
+                let weighted = ratings
+                    .iter()
+                    .zip(weights())
+                    .map(|(rating, weight)| rating * weight)
+                    .sum::<f64>();
+                weighted / weights().take(slots).sum::<f64>()
+            }
+        }
+    }
+}
+
+fn weights() -> impl Iterator<Item = f64> {
+    std::iter::successors(Some(1.0), |weight| Some(weight * WEIGHT_DECAY))
+}
+
+/// 当前设置的总Rating定义
+pub fn overall_definition() -> OverallDefinition {
+    let settings = SETTINGS.lock().unwrap();
+    OverallDefinition {
+        mode: settings.overall,
+        slots: settings.overall_slots,
+    }
+}
+
+/// 按当前设置计算一组成绩的总Rating
+pub fn overall_rating<'a>(ratings: impl IntoIterator<Item = &'a RatingInfo>) -> f64 {
+    overall_definition().compute(ratings.into_iter().map(|info| info.rating))
+}
+
+/// 成绩表中的卡片数量。至少30张，N更大时与N相同，界面上的总Rating才与完整成绩计算的一致
+pub fn table_size() -> usize {
+    overall_definition().slots.max(DEFAULT_SLOTS)
+}
+
+/// 成绩表（已按Rating排序）的总Rating
+pub fn b30_average(ratings: &[RatingInfo]) -> f64 {
+    overall_rating(ratings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(mode: OverallRating, slots: usize) -> OverallDefinition {
+        OverallDefinition { mode, slots }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn empty_input_is_zero() {
+        for mode in OverallRating::ALL {
+            for slots in [0, 1, 30, 100] {
+                assert_eq!(definition(mode, slots).compute([]), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn average_divides_by_actual_count_below_slots() {
+        let cases: &[(usize, &[f64], f64)] = &[
+            (30, &[10.0, 8.0, 6.0], 8.0),
+            (2, &[6.0, 10.0, 8.0], 9.0),
+            (1, &[3.0, 5.0], 5.0),
+            (0, &[3.0, 5.0], 5.0),
+        ];
+        for &(slots, ratings, expected) in cases {
+            assert_close(
+                definition(OverallRating::Average, slots).compute(ratings.iter().copied()),
+                expected,
+            );
+        }
+    }
+
+    #[test]
+    fn sum_over_slots_fills_empty_slots_with_zero() {
+        let cases: &[(usize, &[f64], f64)] = &[
+            (30, &[30.0, 30.0], 2.0),
+            (4, &[8.0, 4.0], 3.0),
+            (2, &[1.0, 9.0, 7.0], 8.0),
+            (1, &[2.0, 6.0], 6.0),
+        ];
+        for &(slots, ratings, expected) in cases {
+            assert_close(
+                definition(OverallRating::SumOverSlots, slots).compute(ratings.iter().copied()),
+                expected,
+            );
+        }
+    }
+
+    #[test]
+    fn weighted_uses_decaying_weights_over_all_slots() {
+        let weight_sum = |slots: usize| weights().take(slots).sum::<f64>();
+
+        // N=1只取最高的成绩
+        assert_close(
+            definition(OverallRating::Weighted, 1).compute([4.0, 7.0, 5.0]),
+            7.0,
+        );
+
+        // 成绩都相同且填满N个位置时等于该成绩
+        for slots in [1, 30, 100] {
+            assert_close(
+                definition(OverallRating::Weighted, slots).compute(vec![12.0; slots + 5]),
+                12.0,
+            );
+        }
+
+        // 不足N个成绩时空位按0计算
+        for slots in [30, 100] {
+            let expected = (10.0 + 8.0 * WEIGHT_DECAY) / weight_sum(slots);
+            assert_close(
+                definition(OverallRating::Weighted, slots).compute([8.0, 10.0]),
+                expected,
+            );
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::tr;
+use std::io;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use tokio_util::sync::CancellationToken;
+
+/// 读取osu!文件夹所处的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IngestionStage {
+    /// 正在解析osu!.db和scores.db，无法得知进度
+    Databases,
+    /// 正在处理scores.db中的成绩
+    Scores,
+    /// 正在读取.osu文件并计算星级
+    Beatmaps,
+    /// 正在按玩家整理成绩
+    Finishing,
+}
+
+/// 读取osu!文件夹的进度，可以在其他线程中读取，也可以用来取消读取
+#[derive(Debug)]
+pub struct IngestionProgress {
+    stage: AtomicU8,
+    scores_done: AtomicUsize,
+    scores_total: AtomicUsize,
+    maps_parsed: AtomicUsize,
+    srs_done: AtomicUsize,
+    maps_total: AtomicUsize,
+    cancel: CancellationToken,
+}
+
+/// 某一时刻的进度
+#[derive(Debug, Clone, Copy)]
+pub struct ProgressSnapshot {
+    pub stage: IngestionStage,
+    pub scores_done: usize,
+    pub scores_total: usize,
+    pub maps_parsed: usize,
+    pub srs_done: usize,
+    pub maps_total: usize,
+}
+
+impl Default for IngestionProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IngestionProgress {
+    pub fn new() -> Self {
+        IngestionProgress {
+            stage: AtomicU8::new(IngestionStage::Databases as u8),
+            scores_done: AtomicUsize::new(0),
+            scores_total: AtomicUsize::new(0),
+            maps_parsed: AtomicUsize::new(0),
+            srs_done: AtomicUsize::new(0),
+            maps_total: AtomicUsize::new(0),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// 开始处理scores.db中的`total`个成绩
+    pub(crate) fn start_scores(&self, total: usize) {
+        self.scores_done.store(0, Ordering::Relaxed);
+        self.scores_total.store(total, Ordering::Relaxed);
+        self.stage
+            .store(IngestionStage::Scores as u8, Ordering::Relaxed);
+    }
+
+    /// 开始读取`maps_total`张谱面，为其上的`scores_total`个成绩计算Rating
+    pub(crate) fn start_beatmaps(&self, maps_total: usize, scores_total: usize) {
+        self.maps_total.store(maps_total, Ordering::Relaxed);
+        self.scores_done.store(0, Ordering::Relaxed);
+        self.scores_total.store(scores_total, Ordering::Relaxed);
+        self.stage
+            .store(IngestionStage::Beatmaps as u8, Ordering::Relaxed);
+    }
+
+    pub(crate) fn finish(&self) {
+        self.stage
+            .store(IngestionStage::Finishing as u8, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_scores(&self, count: usize) {
+        self.scores_done.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn map_parsed(&self) {
+        self.maps_parsed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn sr_computed(&self) {
+        self.srs_done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// 已取消时返回`Interrupted`错误，用于在各个阶段之间提前退出
+    pub(crate) fn check(&self) -> io::Result<()> {
+        if self.is_cancelled() {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                tr!("读取已取消"),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        let stage = match self.stage.load(Ordering::Relaxed) {
+            0 => IngestionStage::Databases,
+            1 => IngestionStage::Scores,
+            2 => IngestionStage::Beatmaps,
+            _ => IngestionStage::Finishing,
+        };
+        ProgressSnapshot {
+            stage,
+            scores_done: self.scores_done.load(Ordering::Relaxed),
+            scores_total: self.scores_total.load(Ordering::Relaxed),
+            maps_parsed: self.maps_parsed.load(Ordering::Relaxed),
+            srs_done: self.srs_done.load(Ordering::Relaxed),
+            maps_total: self.maps_total.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl ProgressSnapshot {
+    /// 总体进度，0到1之间，处理scores.db占前20%。解析数据库时无法得知进度，返回None
+    pub fn fraction(&self) -> Option<f32> {
+        let ratio = |done: usize, total: usize| {
+            if total == 0 {
+                1.0
+            } else {
+                (done as f32 / total as f32).min(1.0)
+            }
+        };
+        match self.stage {
+            IngestionStage::Databases => None,
+            IngestionStage::Scores => Some(0.2 * ratio(self.scores_done, self.scores_total)),
+            IngestionStage::Beatmaps => {
+                let done = self.maps_parsed + self.srs_done + self.scores_done;
+                let total = 2 * self.maps_total + self.scores_total;
+                Some(0.2 + 0.8 * ratio(done, total))
+            }
+            IngestionStage::Finishing => Some(1.0),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self.stage {
+            IngestionStage::Databases => tr!("正在读取osu!.db和scores.db..."),
+            IngestionStage::Scores => {
+                tr!("正在处理成绩 {}/{}", self.scores_done, self.scores_total)
+            }
+            IngestionStage::Beatmaps => tr!(
+                "已读取谱面 {}/{}，已计算星级 {}/{}，已处理成绩 {}/{}",
+                self.maps_parsed,
+                self.maps_total,
+                self.srs_done,
+                self.maps_total,
+                self.scores_done,
+                self.scores_total
+            ),
+            IngestionStage::Finishing => tr!("正在整理成绩..."),
+        }
+    }
+}
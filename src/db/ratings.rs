@@ -1,22 +1,87 @@
 use chrono::{DateTime, Utc};
 // use chrono::Local;
 // use colored::Colorize;
-// use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
 use osu_db::{self, Listing, Mod, ModSet, Replay, ScoreList};
 use rayon::prelude::*;
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::SystemTime;
 use walkdir::WalkDir;
 
+use crate::db::algorithm::algorithm_stamp;
+use crate::db::dedupe::dedupe_ratings;
+use crate::db::progress::IngestionProgress;
+use crate::db::report::{IngestionReport, SkipReason, SkippedMap, SkippedScore};
+use crate::db::status::{RankedStatus, counts_for_b30, load_status_mirror, update_beatmap_status};
 use crate::db::{BeatmapStoreInfo, PlayRecord, RatingInfo};
 use crate::db::{get_db_path, get_osu_install_path, get_replay_timestamp};
+use crate::tr;
+use mania_converter::BeatMapInfo;
 use mania_converter::osu_func::{OsuDataV128, calculate_from_data};
 
 use super::RatingMapInfo;
 
-pub fn extract_plays(osu_exe_dir: &str) -> io::Result<HashMap<String, BeatmapStoreInfo>> {
+/// 从osu!.db读取的谱面列表，文件没有变化时不必重新读取
+struct CachedListing {
+    path: PathBuf,
+    modified: SystemTime,
+    /// 6K谱面的(hash, 路径, osu!.db中的状态)
+    maps: Vec<(String, PathBuf, RankedStatus)>,
+    known_hashes: HashSet<String>,
+}
+
+static LISTING_CACHE: LazyLock<Mutex<Option<Arc<CachedListing>>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+fn load_listing(osu_path: &Path, db_path: Option<PathBuf>) -> io::Result<Arc<CachedListing>> {
+    let db_path = db_path.ok_or(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "cannot find osu!.exe",
+    ))?;
+    let modified = fs::metadata(&db_path)?.modified()?;
+    if let Some(cached) = LISTING_CACHE.lock().unwrap().as_ref()
+        && cached.path == db_path
+        && cached.modified == modified
+    {
+        return Ok(cached.clone());
+    }
+
+    let listing =
+        Listing::from_file(&db_path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut maps = Vec::new();
+    let mut known_hashes = HashSet::new();
+    for b in listing.beatmaps {
+        if let Some(hash) = &b.hash {
+            known_hashes.insert(hash.clone());
+        }
+        if b.circle_size == 6.0
+            && b.mode == osu_db::Mode::Mania
+            && let (Some(folder_name), Some(file_name), Some(hash)) =
+                (b.folder_name, b.file_name, b.hash)
+        {
+            let path = osu_path.join("Songs").join(folder_name).join(file_name);
+            maps.push((hash, path, RankedStatus::from_osu_db(b.status)));
+        }
+    }
+    let cached = Arc::new(CachedListing {
+        path: db_path,
+        modified,
+        maps,
+        known_hashes,
+    });
+    *LISTING_CACHE.lock().unwrap() = Some(cached.clone());
+    Ok(cached)
+}
+
+/// 读取osu!.db和scores.db，返回6K谱面及其游玩记录，以及因为谱面不在osu!.db中而跳过的成绩
+pub fn extract_plays(
+    osu_exe_dir: &str,
+    progress: &IngestionProgress,
+) -> io::Result<(HashMap<String, BeatmapStoreInfo>, Vec<SkippedScore>)> {
     // 读取谱面数据库
     let osu_path = if osu_exe_dir.is_empty() {
         get_osu_install_path().ok_or(io::Error::new(
@@ -26,43 +91,42 @@ pub fn extract_plays(osu_exe_dir: &str) -> io::Result<HashMap<String, BeatmapSto
     } else {
         PathBuf::from(osu_exe_dir)
     };
-    println!("osu!.exe所在文件夹路径：{osu_path:?}");
-    let listing = Listing::from_file(get_db_path(osu_exe_dir, "osu!.db").ok_or(io::Error::new(
-        io::ErrorKind::InvalidData,
-        "cannot find osu!.exe",
-    ))?)
-    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    log::info!("osu!.exe所在文件夹路径：{osu_path:?}");
+    let listing = load_listing(&osu_path, get_db_path(osu_exe_dir, "osu!.db"))?;
     let scores = ScoreList::from_file(get_db_path(osu_exe_dir, "scores.db").ok_or(
         io::Error::new(io::ErrorKind::InvalidData, "cannot find osu!.exe"),
     )?)
     .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    progress.check()?;
     // 构建哈希映射存储谱面信息
     let mut beatmap_store: HashMap<String, BeatmapStoreInfo> = HashMap::new();
-
-    for b in listing.beatmaps {
-        if b.circle_size == 6.0
-            && b.mode == osu_db::Mode::Mania
-            && b.folder_name.is_some()
-            && b.file_name.is_some()
-            && b.hash.is_some()
-        {
-            let path = osu_path
-                .join("Songs")
-                .join(b.folder_name.unwrap())
-                .join(b.file_name.unwrap());
-
-            let info = BeatmapStoreInfo {
-                path,
-                plays: Vec::new(),
-            };
-
-            beatmap_store.insert(b.hash.unwrap(), info);
-        }
+    // osu!.db中所有谱面的hash，用于区分其他键数的谱面和不存在的谱面
+    let known_hashes = &listing.known_hashes;
+    // 6K谱面的状态，镜像文件中的状态优先
+    let status_overrides = load_status_mirror();
+    let mut statuses: HashMap<String, RankedStatus> = HashMap::new();
+
+    for (hash, path, status) in &listing.maps {
+        let status = status_overrides.get(hash).copied().unwrap_or(*status);
+        statuses.insert(hash.clone(), status);
+        let info = BeatmapStoreInfo {
+            path: path.clone(),
+            status,
+            plays: Vec::new(),
+        };
+        beatmap_store.insert(hash.clone(), info);
     }
+    update_beatmap_status(statuses);
 
     // 处理每一条游玩记录
+    let mut skipped_scores = Vec::new();
+    let mut latest_timestamp = DateTime::<Utc>::default();
+    progress.start_scores(scores.beatmaps.iter().map(|b| b.scores.len()).sum());
     for score in scores.beatmaps {
+        progress.check()?;
+        progress.add_scores(score.scores.len());
         for rep in score.scores {
+            latest_timestamp = latest_timestamp.max(rep.timestamp);
             if rep.mode == osu_db::Mode::Mania
                 && rep.beatmap_hash.is_some()
                 && rep.player_name.is_some()
@@ -115,7 +179,8 @@ pub fn extract_plays(osu_exe_dir: &str) -> io::Result<HashMap<String, BeatmapSto
                 // }
 
                 // 更新对应谱面的记录
-                if let Some(info) = beatmap_store.get_mut(&rep.beatmap_hash.unwrap()) {
+                let hash = rep.beatmap_hash.unwrap();
+                if let Some(info) = beatmap_store.get_mut(&hash) {
                     let judgement_vec = [
                         rep.count_geki as u32,
                         rep.count_300 as u32,
@@ -131,22 +196,48 @@ pub fn extract_plays(osu_exe_dir: &str) -> io::Result<HashMap<String, BeatmapSto
                         accuracy,
                         accuracy_rating,
                         timestamp: rep.timestamp,
+                        failed: false,
+                    });
+                } else if !known_hashes.contains(&hash) {
+                    skipped_scores.push(SkippedScore {
+                        hash,
+                        path: None,
+                        player: rep.player_name.unwrap(),
+                        timestamp: rep.timestamp,
+                        accuracy_rating,
+                        reason: SkipReason::NotInOsuDb,
                     });
                 }
             }
         }
     }
 
-    Ok(beatmap_store)
+    // osu!只在退出时写入scores.db，游戏过程中新的成绩只存在于Data/r的回放中
+    for (hash, play) in extract_unstored_replays(osu_exe_dir, latest_timestamp)? {
+        if let Some(info) = beatmap_store.get_mut(&hash) {
+            info.plays.push(play);
+        } else if !known_hashes.contains(&hash) {
+            skipped_scores.push(SkippedScore {
+                hash,
+                path: None,
+                player: play.player,
+                timestamp: play.timestamp,
+                accuracy_rating: play.accuracy_rating,
+                reason: SkipReason::NotInOsuDb,
+            });
+        }
+    }
+
+    Ok((beatmap_store, skipped_scores))
 }
 
 /// 从 osu目录/Data/r 目录下面找到比scores.db中更新的但还未写入的回放，计入信息中
 /// 流程：遍历回放目录的文件名，筛选时间戳大于scores.db中最新时间戳的osr文件，记录其中信息
-#[allow(dead_code)]
+/// 返回值为(谱面hash, 游玩记录)
 pub fn extract_unstored_replays(
     osu_exe_dir: &str,
     timestamp: DateTime<Utc>,
-) -> io::Result<Vec<PlayRecord>> {
+) -> io::Result<Vec<(String, PlayRecord)>> {
     let osu_path = if osu_exe_dir.is_empty() {
         get_osu_install_path().ok_or(io::Error::new(
             io::ErrorKind::InvalidData,
@@ -159,15 +250,18 @@ pub fn extract_unstored_replays(
     let timestamp = get_replay_timestamp(timestamp);
 
     let replay_dir = osu_path.join("Data").join("r");
-    let record_vec: Vec<PlayRecord> = WalkDir::new(&replay_dir)
+    if !replay_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let record_vec: Vec<(String, PlayRecord)> = WalkDir::new(&replay_dir)
+        .max_depth(1)
         .into_iter()
         .filter_map(|entry| entry.ok())
         .filter(|entry| {
             let path = entry.path();
-            let (file_stem, ext) = (path.file_stem(), path.extension().unwrap().to_str());
+            let (file_stem, ext) = (path.file_stem(), path.extension().and_then(|e| e.to_str()));
             let timestamp_osr = file_stem
-                .unwrap()
-                .to_str()
+                .and_then(|s| s.to_str())
                 .unwrap_or("")
                 .split("-")
                 .last()
@@ -219,7 +313,7 @@ pub fn extract_unstored_replays(
                     (acc, acc_r)
                 };
 
-                Some(PlayRecord {
+                let play = PlayRecord {
                     player: rep.player_name?,
                     mods: rep.mods,
                     judgement_num: [
@@ -233,7 +327,9 @@ pub fn extract_unstored_replays(
                     accuracy,
                     accuracy_rating,
                     timestamp: rep.timestamp,
-                })
+                    failed: false,
+                };
+                Some((rep.beatmap_hash?, play))
             } else {
                 None
             }
@@ -243,7 +339,7 @@ pub fn extract_unstored_replays(
 }
 
 #[inline]
-fn calc_rating(diff_const: f64, acc: f64) -> f64 {
+pub(crate) fn calc_rating(diff_const: f64, acc: f64) -> f64 {
     if !(0.0..=100.0).contains(&acc) {
         return 0.0;
     }
@@ -267,150 +363,418 @@ fn calc_rating(diff_const: f64, acc: f64) -> f64 {
     }
 }
 
+/// 由已经考虑了mod的星级计算(定数, Rating)
 #[inline]
-pub fn calc_mod_rating(mods: ModSet, srs: (f64, f64, f64), acc: f64) -> (f64, f64) {
-    let sr_mod = if mods.contains(Mod::HalfTime) {
-        srs.0
-    } else if mods.contains(Mod::DoubleTime) || mods.contains(Mod::Nightcore) {
-        srs.2
-    } else {
-        srs.1
-    };
-    let diff_const = sr_mod * 200.0 / 81.0 + 7.0 / 6.0;
+pub fn calc_sr_rating(sr: f64, acc: f64) -> (f64, f64) {
+    let diff_const = sr * 200.0 / 81.0 + 7.0 / 6.0;
     let rating = calc_rating(diff_const, acc);
     (diff_const, rating)
 }
 
-pub fn extract_ratings(osu_exe_dir: &str) -> io::Result<(Vec<RatingInfo>, Vec<RatingInfo>)> {
+/// 一次游玩需要的星级种类。变速mod优先，与HR/EZ同时开启时使用原谱面的变速星级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SrVariant {
+    NoMod,
+    HalfTime,
+    DoubleTime,
+    HardRock,
+    Easy,
+}
+
+impl SrVariant {
+    pub const ALL: [SrVariant; 5] = [
+        SrVariant::HalfTime,
+        SrVariant::NoMod,
+        SrVariant::DoubleTime,
+        SrVariant::HardRock,
+        SrVariant::Easy,
+    ];
+
+    /// 详情中显示的mod简称
+    pub fn label(self) -> &'static str {
+        match self {
+            SrVariant::NoMod => "NM",
+            SrVariant::HalfTime => "HT",
+            SrVariant::DoubleTime => "DT",
+            SrVariant::HardRock => "HR",
+            SrVariant::Easy => "EZ",
+        }
+    }
+
+    /// 计算星级时的速率
+    pub fn rate(self) -> f64 {
+        match self {
+            SrVariant::HalfTime => 0.75,
+            SrVariant::DoubleTime => 1.5,
+            _ => 1.0,
+        }
+    }
+
+    /// HR和EZ改变判定区间，返回对应的新OD，其他mod返回None
+    pub fn adjusted_od(self, od: f64) -> Option<f64> {
+        // 可以认为：HR mod使判定区间除以1.4, EZ mod使判定区间乘以1.4
+        // 那么需要重新拓扑OD到判定区间的函数，得到新判定区间对应的OD，以HR为例：
+        // window = 64.5 - (original_od * 3.0).ceil()
+        // new_window = window / 1.4
+        // new_od = (64.5 - new_window) / 3.0
+        let window = 64.5 - (od * 3.0).ceil();
+        let new_window = match self {
+            SrVariant::HardRock => window / 1.4,
+            SrVariant::Easy => window * 1.4,
+            _ => return None,
+        };
+        Some((64.5 - new_window) / 3.0)
+    }
+
+    fn from_mods(mods: ModSet) -> Self {
+        if mods.contains(Mod::HalfTime) {
+            SrVariant::HalfTime
+        } else if mods.contains(Mod::DoubleTime) || mods.contains(Mod::Nightcore) {
+            SrVariant::DoubleTime
+        } else if mods.contains(Mod::HardRock) {
+            SrVariant::HardRock
+        } else if mods.contains(Mod::Easy) {
+            SrVariant::Easy
+        } else {
+            SrVariant::NoMod
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedMap {
+    path: PathBuf,
+    info: BeatMapInfo,
+    srs: HashMap<SrVariant, f64>,
+}
+
+/// 谱面信息和星级的缓存，键为谱面hash。文件夹变化后重新读取时只需计算新出现的谱面和mod组合。
+/// 缓存只在内存中，程序运行期间算法不会变化，因此不记录算法版本；
+/// 持久化并带有版本的只有实时模式记录和卡片的磁盘缓存
+static MAP_CACHE: LazyLock<Mutex<HashMap<String, CachedMap>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn compute_map_srs(
+    hash: &str,
+    path: &Path,
+    variants: &HashSet<SrVariant>,
+    progress: &IngestionProgress,
+) -> Result<(BeatMapInfo, HashMap<SrVariant, f64>), SkipReason> {
+    if let Some(cached) = MAP_CACHE.lock().unwrap().get(hash)
+        && cached.path == path
+        && variants.iter().all(|v| cached.srs.contains_key(v))
+    {
+        progress.map_parsed();
+        return Ok((cached.info.clone(), cached.srs.clone()));
+    }
+
+    // 无论能否读取都计入进度
+    progress.map_parsed();
+    if !path.is_file() {
+        return Err(SkipReason::FileMissing);
+    }
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| SkipReason::Unreadable(tr!("路径包含无效字符")))?;
+    let osu_data = match OsuDataV128::from_file(path_str) {
+        Ok(osu_data) => osu_data.to_legacy(),
+        Err(e) => {
+            log::warn!("无法读取谱面文件 {}: {e}", path.display());
+            return Err(SkipReason::Unreadable(e.to_string()));
+        }
+    };
+    let beatmap_info = osu_data.to_beatmap_info(true);
+    let mut srs = HashMap::new();
+    for variant in variants {
+        let sr = match variant {
+            SrVariant::NoMod => beatmap_info.sr.unwrap_or(0.0),
+            SrVariant::HalfTime => calculate_from_data(&osu_data, 0.75)
+                .map_err(|_| SkipReason::SrFailed("HT".into()))?,
+            SrVariant::DoubleTime => calculate_from_data(&osu_data, 1.5)
+                .map_err(|_| SkipReason::SrFailed("DT".into()))?,
+            SrVariant::HardRock | SrVariant::Easy => {
+                let mut new_data = osu_data.clone();
+                if let Some(od) = variant.adjusted_od(new_data.misc.od as f64) {
+                    new_data.misc.od = od as _;
+                }
+                calculate_from_data(&new_data, 1.0)
+                    .map_err(|_| SkipReason::SrFailed(variant.label().into()))?
+            }
+        };
+        srs.insert(*variant, sr);
+    }
+
+    MAP_CACHE.lock().unwrap().insert(
+        hash.to_string(),
+        CachedMap {
+            path: path.to_path_buf(),
+            info: beatmap_info.clone(),
+            srs: srs.clone(),
+        },
+    );
+    Ok((beatmap_info, srs))
+}
+
+/// 用当前的算法重新计算一个成绩，返回(谱面信息, 定数, Rating)
+pub(crate) fn rate_play(
+    hash: &str,
+    path: &Path,
+    mods: ModSet,
+    acc: f64,
+) -> Option<(BeatMapInfo, f64, f64)> {
+    let variant = SrVariant::from_mods(mods);
+    let (info, srs) = compute_map_srs(
+        hash,
+        path,
+        &HashSet::from([variant]),
+        &IngestionProgress::new(),
+    )
+    .ok()?;
+    let (diff_const, rating) = calc_sr_rating(srs.get(&variant).copied().unwrap_or(0.0), acc);
+    Some((info, diff_const, rating))
+}
+
+/// 实时模式使用：按与读取scores.db相同的方式计算一次游玩对应mod的星级，返回(谱面信息, 星级)。
+/// 不是6K或者开启了Random时返回None
+pub fn play_sr(hash: &str, path: &Path, mods: ModSet) -> Option<(BeatMapInfo, f64)> {
+    if mods.contains(Mod::Random) {
+        return None;
+    }
+    let osu_data = match OsuDataV128::from_file(path.to_str()?) {
+        Ok(osu_data) => osu_data.to_legacy(),
+        Err(e) => {
+            // 不是mania谱面时经常读取失败
+            log::debug!("Failed to parse beatmap {}: {e}", path.display());
+            return None;
+        }
+    };
+    if osu_data.misc.circle_size != 6 {
+        return None;
+    }
+    let variant = SrVariant::from_mods(mods);
+    let (info, srs) = compute_map_srs(
+        hash,
+        path,
+        &HashSet::from([variant]),
+        &IngestionProgress::new(),
+    )
+    .ok()?;
+    Some((info, srs.get(&variant).copied().unwrap_or(0.0)))
+}
+
+/// 计算所有成绩的Rating，返回(所有成绩, 每张谱面的最佳成绩, 读取报告)
+pub fn extract_ratings(
+    osu_exe_dir: &str,
+    progress: &IngestionProgress,
+) -> io::Result<(Vec<RatingInfo>, Vec<RatingInfo>, IngestionReport)> {
+    let mut report = IngestionReport {
+        osu_dir: osu_exe_dir.to_string(),
+        algorithm: algorithm_stamp().to_string(),
+        generated_at: Utc::now(),
+        ..Default::default()
+    };
+
     // 读取谱面数据库
-    let beatmap_store: Vec<(String, BeatmapStoreInfo)> = extract_plays(osu_exe_dir)?
-        .into_par_iter()
+    let (plays, skipped_scores) = extract_plays(osu_exe_dir, progress)?;
+    report.scores_total =
+        plays.values().map(|info| info.plays.len()).sum::<usize>() + skipped_scores.len();
+    report.skipped_scores = skipped_scores;
+
+    let beatmap_store: Vec<(String, BeatmapStoreInfo)> = plays
+        .into_iter()
         .filter_map(|(hash, mut info)| {
-            info.plays
-                .retain(|p| !p.mods.contains(Mod::Random) && !p.mods.contains(Mod::Easy));
+            for play in info.plays.extract_if(.., |p| {
+                p.mods.contains(Mod::Random) || p.mods.contains(Mod::Easy)
+            }) {
+                let excluded = if play.mods.contains(Mod::Random) {
+                    "Random"
+                } else {
+                    "Easy"
+                };
+                report.skipped_scores.push(SkippedScore {
+                    hash: hash.clone(),
+                    path: Some(info.path.clone()),
+                    player: play.player,
+                    timestamp: play.timestamp,
+                    accuracy_rating: play.accuracy_rating,
+                    reason: SkipReason::ExcludedMod(excluded.into()),
+                });
+            }
             if info.plays.is_empty() {
                 return None;
             }
             Some((hash, info))
         })
         .collect();
-
-    let processed: Vec<(Vec<RatingInfo>, usize)> = beatmap_store
-        .into_par_iter()
-        // .progress_with(pb)
-        // .with_style(style)
-        .filter_map(|(hash, info)| {
-            // println!("{:?}", info.path);
-            // 可以认为：HR mod使判定区间除以1.4, EZ mod使判定区间乘以1.4
-            // 那么需要重新拓扑OD到判定区间的函数，得到新判定区间对应的OD，以HR为例：
-            // window = 64.5 - (original_od * 3.0).ceil()
-            // new_window = window / 1.4
-            // new_od = (64.5 - new_window) / 3.0
-            let osu_data = OsuDataV128::from_file(info.path.to_str()?)
-                .ok()?
-                .to_legacy();
-            let beatmap_info = osu_data.to_beatmap_info(true);
-
-            let mut mod_flag = (false, false, false);
-            for p in info.plays.iter() {
-                if p.mods.contains(Mod::HalfTime) {
-                    mod_flag.0 = true;
-                } else if p.mods.contains(Mod::DoubleTime) || p.mods.contains(Mod::Nightcore) {
-                    mod_flag.2 = true;
-                } else {
-                    mod_flag.1 = true;
+    report.maps_total = beatmap_store.len();
+    progress.start_beatmaps(
+        beatmap_store.len(),
+        beatmap_store.iter().map(|(_, info)| info.plays.len()).sum(),
+    );
+
+    let processed: Vec<Result<(Vec<RatingInfo>, usize), (SkippedMap, Vec<SkippedScore>)>> =
+        beatmap_store
+            .into_par_iter()
+            .filter_map(|(hash, info)| {
+                // 取消后剩下的谱面直接跳过
+                if progress.is_cancelled() {
+                    return None;
+                }
+                let rated = rate_map_plays(&hash, &info, progress);
+                progress.sr_computed();
+                progress.add_scores(info.plays.len());
+                let all_plays = match rated {
+                    Ok(v) => v,
+                    Err(reason) => return Some(Err(skip_map(hash, info, reason))),
                 };
-            }
 
-            let sr = beatmap_info.sr.unwrap_or(0.0);
-            let (sr_ht, sr_dt) = (
-                if mod_flag.0 {
-                    calculate_from_data(&osu_data, 0.75).ok()?
-                } else {
-                    0.0
-                },
-                if mod_flag.2 {
-                    calculate_from_data(&osu_data, 1.5).ok()?
-                } else {
-                    0.0
-                },
-            );
-            let srs = (sr_ht, sr, sr_dt);
-
-            // 生成所有play记录
-            let all_plays: Vec<RatingInfo> = info
-                .plays
-                .iter()
-                .map(|play| {
-                    let srs = if play.mods.contains(Mod::HardRock) || play.mods.contains(Mod::Easy)
-                    {
-                        let mut new_data = osu_data.clone();
-                        let original_od = new_data.misc.od;
-                        let window = 64.5 - (original_od * 3.0).ceil();
-                        let new_window = if play.mods.contains(Mod::HardRock) {
-                            window / 1.4
-                        } else {
-                            window * 1.4
-                        };
-                        let new_od = (64.5 - new_window) / 3.0;
-                        new_data.misc.od = new_od;
-                        let (sr_ht, sr_dt) = (
-                            if mod_flag.0 {
-                                calculate_from_data(&osu_data, 0.75).unwrap_or(0.0)
-                            } else {
-                                0.0
-                            },
-                            if mod_flag.2 {
-                                calculate_from_data(&osu_data, 1.5).unwrap_or(0.0)
-                            } else {
-                                0.0
-                            },
-                        );
-                        let sr = calculate_from_data(&new_data, 1.0).unwrap_or(0.0);
-                        (sr_ht, sr, sr_dt)
-                    } else {
-                        srs
-                    };
-                    let (diff_const, rating) =
-                        calc_mod_rating(play.mods, srs, play.accuracy_rating);
-                    RatingInfo {
-                        map_info: RatingMapInfo {
-                            hash: hash.clone(),
-                            path: info.path.clone(),
-                            info: beatmap_info.clone(),
-                        },
-                        score_info: play.clone(),
-                        diff_const,
-                        rating,
-                    }
-                })
-                .collect();
-
-            let best_play_index = all_plays
-                .iter()
-                .enumerate()
-                .max_by(|(_, a), (_, b)| a.rating.partial_cmp(&b.rating).unwrap_or(Ordering::Equal))
-                .map(|(i, _)| i)?;
-
-            Some((all_plays, best_play_index))
-        })
-        .collect();
+                let best_play_index = all_plays
+                    .iter()
+                    .enumerate()
+                    .max_by(|(_, a), (_, b)| {
+                        a.rating.partial_cmp(&b.rating).unwrap_or(Ordering::Equal)
+                    })
+                    .map(|(i, _)| i)?;
+
+                Some(Ok((all_plays, best_play_index)))
+            })
+            .collect();
+    progress.check()?;
+
+    let mut loaded = Vec::new();
+    for result in processed {
+        match result {
+            Ok(v) => loaded.push(v),
+            Err((map, scores)) => {
+                report.skipped_maps.push(map);
+                report.skipped_scores.extend(scores);
+            }
+        }
+    }
+    report.maps_loaded = loaded.len();
 
-    let all_ratings: Vec<_> = processed
+    let all_ratings: Vec<_> = loaded
         .iter()
         .flat_map(|(plays, _)| plays.iter().cloned())
         .collect();
-    let best_ratings: Vec<_> = processed
+    let best_ratings: Vec<_> = loaded
         .into_iter()
         .map(|(plays, i)| plays[i].clone())
         .collect();
+    report.scores_loaded = all_ratings.len();
+
+    Ok((all_ratings, best_ratings, report))
+}
+
+/// 计算一张谱面上所有游玩记录的Rating
+fn rate_map_plays(
+    hash: &str,
+    info: &BeatmapStoreInfo,
+    progress: &IngestionProgress,
+) -> Result<Vec<RatingInfo>, SkipReason> {
+    let variants = info
+        .plays
+        .iter()
+        .map(|p| SrVariant::from_mods(p.mods))
+        .collect::<HashSet<_>>();
+    let (beatmap_info, srs) = compute_map_srs(hash, &info.path, &variants, progress)?;
+    Ok(info
+        .plays
+        .iter()
+        .map(|play| {
+            let sr = srs
+                .get(&SrVariant::from_mods(play.mods))
+                .copied()
+                .unwrap_or(0.0);
+            let (diff_const, rating) = calc_sr_rating(sr, play.accuracy_rating);
+            RatingInfo {
+                map_info: RatingMapInfo {
+                    hash: hash.to_string(),
+                    path: info.path.clone(),
+                    info: beatmap_info.clone(),
+                    status: info.status,
+                },
+                score_info: play.clone(),
+                diff_const,
+                rating,
+            }
+        })
+        .collect())
+}
+
+/// 只计算`known`中还没有的成绩，用于scores.db或回放变化时的增量刷新。
+/// osu!.db没有变化时谱面列表和星级都来自缓存。被删除的成绩不会被发现，需要完整读取
+pub fn extract_new_ratings(osu_exe_dir: &str, known: &[RatingInfo]) -> io::Result<Vec<RatingInfo>> {
+    let progress = IngestionProgress::new();
+    let (plays, _) = extract_plays(osu_exe_dir, &progress)?;
+    let known = known
+        .iter()
+        .map(|info| {
+            (
+                info.map_info.hash.as_str(),
+                info.score_info.player.as_str(),
+                info.score_info.timestamp,
+            )
+        })
+        .collect::<HashSet<_>>();
+    let new_ratings = plays
+        .into_par_iter()
+        .filter_map(|(hash, mut info)| {
+            info.plays.retain(|p| {
+                !p.mods.contains(Mod::Random)
+                    && !p.mods.contains(Mod::Easy)
+                    && !known.contains(&(hash.as_str(), p.player.as_str(), p.timestamp))
+            });
+            if info.plays.is_empty() {
+                return None;
+            }
+            match rate_map_plays(&hash, &info, &progress) {
+                Ok(ratings) => Some(ratings),
+                Err(reason) => {
+                    log::warn!("Skipped new scores on {hash}: {reason:?}");
+                    None
+                }
+            }
+        })
+        .flatten()
+        .collect();
+    Ok(new_ratings)
+}
 
-    Ok((all_ratings, best_ratings))
+/// 谱面无法计算时，谱面和其上的所有成绩都记入报告
+fn skip_map(
+    hash: String,
+    info: BeatmapStoreInfo,
+    reason: SkipReason,
+) -> (SkippedMap, Vec<SkippedScore>) {
+    let scores = info
+        .plays
+        .iter()
+        .map(|play| SkippedScore {
+            hash: hash.clone(),
+            path: Some(info.path.clone()),
+            player: play.player.clone(),
+            timestamp: play.timestamp,
+            accuracy_rating: play.accuracy_rating,
+            reason: reason.clone(),
+        })
+        .collect();
+    let map = SkippedMap {
+        hash,
+        play_count: info.plays.len(),
+        path: info.path,
+        reason,
+    };
+    (map, scores)
 }
 
-pub fn prepare_ratings(osu_exe_dir: &str) -> io::Result<HashMap<String, Vec<RatingInfo>>> {
+pub fn prepare_ratings(
+    osu_exe_dir: &str,
+    progress: &IngestionProgress,
+) -> io::Result<(HashMap<String, Vec<RatingInfo>>, IngestionReport)> {
     // 读取谱面数据库
-    let (mut all_ratings, mut best_ratings) = extract_ratings(osu_exe_dir)?;
+    let (mut all_ratings, best_ratings, report) = extract_ratings(osu_exe_dir, progress)?;
+    progress.finish();
 
     // Recent Scores
     all_ratings.sort_unstable_by(|a, b| {
@@ -421,8 +785,7 @@ pub fn prepare_ratings(osu_exe_dir: &str) -> io::Result<HashMap<String, Vec<Rati
     });
     let all_ratings_clone = all_ratings.clone();
 
-    best_ratings
-        .sort_unstable_by(|a, b| b.rating.partial_cmp(&a.rating).unwrap_or(Ordering::Equal));
+    let best_ratings = dedupe_ratings(best_ratings);
 
     // 玩家列表
     let players_vec = {
@@ -433,7 +796,7 @@ pub fn prepare_ratings(osu_exe_dir: &str) -> io::Result<HashMap<String, Vec<Rati
         players.into_iter().collect::<Vec<_>>()
     };
 
-    // 对每个玩家，生成Rating列表，对于谱面Hash相同的选择最高分数
+    // 对每个玩家，生成Rating列表，按设置的去重方式只保留最高的成绩
     let mut player_scores = players_vec
         .into_iter()
         .map(|player| {
@@ -441,32 +804,78 @@ pub fn prepare_ratings(osu_exe_dir: &str) -> io::Result<HashMap<String, Vec<Rati
                 .extract_if(.., |rating| rating.score_info.player == player)
                 .collect::<Vec<_>>();
 
-            let mut hash_ratings: HashMap<String, RatingInfo> = HashMap::new();
-            player_ratings_origin.iter().for_each(|info| {
-                let entry = hash_ratings
-                    .entry(info.map_info.hash.clone())
-                    .or_insert(info.clone());
-                if info.rating > entry.rating {
-                    *entry = info.clone();
-                }
-            });
-
-            let mut player_ratings_final = hash_ratings.into_values().collect::<Vec<_>>();
-            player_ratings_final.sort_unstable_by(|a, b| {
-                b.rating.partial_cmp(&a.rating).unwrap_or(Ordering::Equal)
-            });
-
-            let final_name = match player.as_str() {
-                "Recent 30" | "All Players" => format!("{player} (Player)"),
-                _ => player,
-            };
-
-            (final_name, player_ratings_final)
+            (
+                player_list_name(player),
+                dedupe_ratings(player_ratings_origin),
+            )
         })
         .collect::<HashMap<_, _>>();
 
     player_scores.insert("[Recent 30]".into(), all_ratings_clone);
     player_scores.insert("[All Players]".into(), best_ratings);
 
-    Ok(player_scores)
+    Ok((player_scores, report))
+}
+
+/// 玩家名与[Recent 30]等特殊列表重名时加上后缀
+pub fn player_list_name(player: String) -> String {
+    match player.as_str() {
+        "Recent 30" | "All Players" => format!("{player} (Player)"),
+        _ => player,
+    }
+}
+
+/// 对于谱面Hash相同的成绩只保留Rating最高的一个，并按Rating从高到低排序。
+/// 开启只计入Ranked/Loved谱面时，其他谱面的成绩被去掉
+pub(crate) fn best_per_hash(ratings: Vec<RatingInfo>) -> Vec<RatingInfo> {
+    let mut hash_ratings: HashMap<String, RatingInfo> = HashMap::new();
+    for info in ratings
+        .into_iter()
+        .filter(|info| counts_for_b30(info) && !info.score_info.failed)
+    {
+        match hash_ratings.get(&info.map_info.hash) {
+            Some(entry) if entry.rating >= info.rating => {}
+            _ => {
+                hash_ratings.insert(info.map_info.hash.clone(), info);
+            }
+        }
+    }
+
+    let mut ratings_final = hash_ratings.into_values().collect::<Vec<_>>();
+    ratings_final
+        .sort_unstable_by(|a, b| b.rating.partial_cmp(&a.rating).unwrap_or(Ordering::Equal));
+    ratings_final
+}
+
+/// 将额外的成绩（如实时模式的记录）合并进玩家成绩表，已经存在的游玩会被跳过。
+/// 返回内容发生变化的列表名称
+pub fn merge_ratings(
+    player_scores: &mut HashMap<String, Vec<RatingInfo>>,
+    ratings: &[RatingInfo],
+) -> HashSet<String> {
+    let mut changed = HashSet::new();
+    // 失败的游玩不会出现在scores.db中，也不合并进成绩表
+    for info in ratings.iter().filter(|info| !info.score_info.failed) {
+        let recent = player_scores.entry("[Recent 30]".into()).or_default();
+        if recent.iter().any(|r| r.is_same_play(info)) {
+            continue;
+        }
+        recent.push(info.clone());
+        changed.insert("[Recent 30]".to_string());
+
+        for list_name in [
+            player_list_name(info.score_info.player.clone()),
+            "[All Players]".to_string(),
+        ] {
+            let list = player_scores.entry(list_name.clone()).or_default();
+            list.push(info.clone());
+            *list = dedupe_ratings(std::mem::take(list));
+            changed.insert(list_name);
+        }
+    }
+
+    if let Some(recent) = player_scores.get_mut("[Recent 30]") {
+        recent.sort_unstable_by(|a, b| b.score_info.timestamp.cmp(&a.score_info.timestamp));
+    }
+    changed
 }
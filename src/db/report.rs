@@ -0,0 +1,186 @@
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::{env, fmt, fs, io};
+
+use crate::tr;
+
+/// 谱面或成绩没有计入Rating的原因
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "reason", content = "detail", rename_all = "snake_case")]
+pub enum SkipReason {
+    /// 成绩对应的谱面不在osu!.db中
+    NotInOsuDb,
+    /// osu!.db中有记录，但Songs文件夹中找不到.osu文件
+    FileMissing,
+    /// .osu文件无法解析
+    Unreadable(String),
+    /// 某个速率的星级计算失败
+    SrFailed(String),
+    /// 开启了不计入Rating的mod
+    ExcludedMod(String),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            SkipReason::NotInOsuDb => {
+                tr!("谱面不在osu!.db中（谱面已删除，或者是本次启动osu!后新下载的谱面）")
+            }
+            SkipReason::FileMissing => tr!("找不到谱面文件（谱面文件夹被移动或删除）"),
+            SkipReason::Unreadable(e) => tr!("谱面文件无法读取: {}", e),
+            SkipReason::SrFailed(rate) => tr!("{}星级计算失败", rate),
+            SkipReason::ExcludedMod(m) => tr!("{} mod不计入Rating", m),
+        };
+        f.write_str(&text)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedMap {
+    pub hash: String,
+    pub path: PathBuf,
+    /// 这张谱面上因此没有计入的成绩数量
+    pub play_count: usize,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedScore {
+    pub hash: String,
+    pub path: Option<PathBuf>,
+    pub player: String,
+    pub timestamp: DateTime<Utc>,
+    pub accuracy_rating: f64,
+    pub reason: SkipReason,
+}
+
+/// 读取osu!文件夹时跳过的谱面和成绩
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IngestionReport {
+    pub osu_dir: String,
+    /// 计算星级和Rating的算法版本
+    pub algorithm: String,
+    pub generated_at: DateTime<Utc>,
+    /// 有游玩记录的6K谱面数量
+    pub maps_total: usize,
+    pub maps_loaded: usize,
+    /// 6K谱面以及找不到谱面的Mania成绩数量
+    pub scores_total: usize,
+    pub scores_loaded: usize,
+    pub skipped_maps: Vec<SkippedMap>,
+    pub skipped_scores: Vec<SkippedScore>,
+}
+
+/// 最近一次读取osu!文件夹的报告
+pub static INGESTION_REPORT: LazyLock<Mutex<IngestionReport>> =
+    LazyLock::new(|| Mutex::new(IngestionReport::default()));
+
+impl IngestionReport {
+    pub fn skipped_count(&self) -> usize {
+        self.skipped_maps.len() + self.skipped_scores.len()
+    }
+
+    pub fn summary(&self) -> String {
+        tr!(
+            "已读取 {}/{} 张谱面，{}/{} 个成绩；跳过 {} 张谱面，{} 个成绩",
+            self.maps_loaded,
+            self.maps_total,
+            self.scores_loaded,
+            self.scores_total,
+            self.skipped_maps.len(),
+            self.skipped_scores.len()
+        )
+    }
+
+    /// 每种原因跳过的数量，按数量从多到少排序
+    pub fn reason_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for reason in self
+            .skipped_maps
+            .iter()
+            .map(|m| &m.reason)
+            .chain(self.skipped_scores.iter().map(|s| &s.reason))
+        {
+            let key = match reason {
+                // 具体的错误信息不参与分类
+                SkipReason::Unreadable(_) => tr!("谱面文件无法读取"),
+                reason => reason.to_string(),
+            };
+            *counts.entry(key).or_default() += 1;
+        }
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// 每个跳过的项目一行，谱面按受影响的成绩数排序，成绩按时间从新到旧排序
+    pub fn item_lines(&self) -> Vec<String> {
+        let mut maps = self.skipped_maps.iter().collect::<Vec<_>>();
+        maps.sort_by(|a, b| b.play_count.cmp(&a.play_count));
+        let mut scores = self.skipped_scores.iter().collect::<Vec<_>>();
+        scores.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+        maps.into_iter()
+            .map(|m| {
+                tr!(
+                    "[谱面] {} ({}个成绩) - {}",
+                    m.path.display(),
+                    m.play_count,
+                    m.reason
+                )
+            })
+            .chain(scores.into_iter().map(|s| {
+                tr!(
+                    "[成绩] {} {} Acc {}% {} - {}",
+                    s.player,
+                    s.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                    format!("{:.02}", s.accuracy_rating),
+                    s.path
+                        .as_ref()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_else(|| s.hash.clone()),
+                    s.reason
+                )
+            }))
+            .collect()
+    }
+
+    /// 导出为程序目录下export文件夹中的文本和JSON，返回文本文件的路径
+    pub fn export(&self) -> io::Result<PathBuf> {
+        let export_dir = env::current_dir()?.join("export");
+        fs::create_dir_all(&export_dir)?;
+        let name = format!(
+            "ingestion-report-{}",
+            self.generated_at
+                .with_timezone(&Local)
+                .format("%Y%m%d-%H%M%S")
+        );
+
+        let mut text = tr!(
+            "osu!文件夹: {}\n生成时间: {}\n算法版本: {}\n{}\n\n",
+            self.osu_dir,
+            self.generated_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            self.algorithm,
+            self.summary()
+        );
+        for (reason, count) in self.reason_counts() {
+            text.push_str(&format!("{count}\t{reason}\n"));
+        }
+        text.push('\n');
+        for line in self.item_lines() {
+            text.push_str(&line);
+            text.push('\n');
+        }
+        let text_path = export_dir.join(format!("{name}.txt"));
+        fs::write(&text_path, text)?;
+
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(export_dir.join(format!("{name}.json")), json)?;
+        Ok(text_path)
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use osu_db::Mod;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::db::RatingInfo;
+use crate::db::dedupe::dedupe_ratings;
+use crate::db::overall::{OverallDefinition, overall_definition};
+use crate::db::ratings::player_list_name;
+
+/// 实时模式一次会话的统计
+#[derive(Debug, Clone)]
+pub struct SessionSummary {
+    pub player: String,
+    pub play_count: usize,
+    /// 按mod变速后的谱面总时长，单位毫秒
+    pub time_played: u32,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub average_rating: f64,
+    pub best_play: RatingInfo,
+    pub b30_before: f64,
+    pub b30_after: f64,
+    /// b30_before和b30_after的计算方式和成绩数量
+    pub overall: OverallDefinition,
+    /// 合并后进入B30（前N名）的本次成绩数量
+    pub new_b30_entries: usize,
+}
+
+/// 统计实时模式的成绩。会话中可能有多个玩家，以游玩次数最多的玩家为准
+pub fn summarize_session(
+    session: &[RatingInfo],
+    player_scores: &HashMap<String, Vec<RatingInfo>>,
+) -> Option<SessionSummary> {
+    let mut play_counts: HashMap<&str, usize> = HashMap::new();
+    for info in session {
+        *play_counts
+            .entry(info.score_info.player.as_str())
+            .or_default() += 1;
+    }
+    let player = play_counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(player, _)| player.to_string())?;
+
+    let plays = session
+        .iter()
+        .filter(|info| info.score_info.player == player)
+        .cloned()
+        .collect::<Vec<_>>();
+    let play_count = plays.len();
+    let time_played = plays
+        .iter()
+        .map(|info| (info.map_info.info.length as f64 / mod_rate(info)) as u32)
+        .sum();
+    let started_at = plays.iter().map(|info| info.score_info.timestamp).min()?;
+    let ended_at = plays.iter().map(|info| info.score_info.timestamp).max()?;
+    // 失败的游玩计入游玩次数和时长，不计入Rating统计
+    let completed = plays
+        .iter()
+        .filter(|info| !info.score_info.failed)
+        .cloned()
+        .collect::<Vec<_>>();
+    let average_rating = if completed.is_empty() {
+        0.0
+    } else {
+        completed.iter().map(|info| info.rating).sum::<f64>() / completed.len() as f64
+    };
+    let by_rating = |a: &&RatingInfo, b: &&RatingInfo| {
+        a.rating.partial_cmp(&b.rating).unwrap_or(Ordering::Equal)
+    };
+    // 全部失败时以失败的游玩作为最佳成绩
+    let best_play = completed
+        .iter()
+        .max_by(by_rating)
+        .or_else(|| plays.iter().max_by(by_rating))?
+        .clone();
+
+    let current = player_scores
+        .get(&player_list_name(player.clone()))
+        .cloned()
+        .unwrap_or_default();
+    let overall = overall_definition();
+    let b30_before = overall.compute(current.iter().map(|info| info.rating));
+    let mut merged = current;
+    merged.extend(completed.iter().cloned());
+    let merged = dedupe_ratings(merged);
+    let b30_after = overall.compute(merged.iter().map(|info| info.rating));
+    let new_b30_entries = merged
+        .iter()
+        .take(overall.slots)
+        .filter(|info| completed.iter().any(|p| p.is_same_score(info)))
+        .count();
+
+    Some(SessionSummary {
+        player,
+        play_count,
+        time_played,
+        started_at,
+        ended_at,
+        average_rating,
+        best_play,
+        b30_before,
+        b30_after,
+        overall,
+        new_b30_entries,
+    })
+}
+
+fn mod_rate(info: &RatingInfo) -> f64 {
+    let mods = info.score_info.mods;
+    if mods.contains(Mod::HalfTime) {
+        0.75
+    } else if mods.contains(Mod::DoubleTime) || mods.contains(Mod::Nightcore) {
+        1.5
+    } else {
+        1.0
+    }
+}
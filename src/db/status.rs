@@ -0,0 +1,258 @@
+use log::Level;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::sync::{LazyLock, Mutex};
+
+use crate::db::RatingInfo;
+use crate::settings::SETTINGS;
+use crate::tr;
+use crate::ui::notify::notify;
+
+/// 谱面在官网的状态。osu!.db不区分Pending、WIP和Graveyard，统一记为Pending，
+/// 只有镜像文件中的状态才会是Graveyard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RankedStatus {
+    #[default]
+    Unknown,
+    Unsubmitted,
+    Pending,
+    Graveyard,
+    Ranked,
+    Approved,
+    Qualified,
+    Loved,
+}
+
+impl RankedStatus {
+    pub fn from_osu_db(status: osu_db::listing::RankedStatus) -> Self {
+        use osu_db::listing::RankedStatus as Db;
+        match status {
+            Db::Unknown => RankedStatus::Unknown,
+            Db::Unsubmitted => RankedStatus::Unsubmitted,
+            Db::PendingWipGraveyard => RankedStatus::Pending,
+            Db::Ranked => RankedStatus::Ranked,
+            Db::Approved => RankedStatus::Approved,
+            Db::Qualified => RankedStatus::Qualified,
+            Db::Loved => RankedStatus::Loved,
+        }
+    }
+
+    /// 解析镜像文件中的状态，支持osu! API v1的approved数值和状态名称
+    fn parse(value: &str) -> Option<Self> {
+        let status = match value.trim().to_ascii_lowercase().as_str() {
+            "4" | "loved" => RankedStatus::Loved,
+            "3" | "qualified" => RankedStatus::Qualified,
+            "2" | "approved" => RankedStatus::Approved,
+            "1" | "ranked" => RankedStatus::Ranked,
+            "0" | "-1" | "pending" | "wip" => RankedStatus::Pending,
+            "-2" | "graveyard" => RankedStatus::Graveyard,
+            "unsubmitted" => RankedStatus::Unsubmitted,
+            _ => return None,
+        };
+        Some(status)
+    }
+
+    /// 卡片上显示的名称，Unknown不显示
+    pub fn label(self) -> String {
+        match self {
+            RankedStatus::Unknown => String::new(),
+            RankedStatus::Unsubmitted => tr!("Unsubmitted"),
+            RankedStatus::Pending => tr!("Pending"),
+            RankedStatus::Graveyard => tr!("Graveyard"),
+            RankedStatus::Ranked => tr!("Ranked"),
+            RankedStatus::Approved => tr!("Approved"),
+            RankedStatus::Qualified => tr!("Qualified"),
+            RankedStatus::Loved => tr!("Loved"),
+        }
+    }
+
+    /// 卡片上徽章的颜色，与官网的配色相近
+    pub fn color(self) -> &'static str {
+        match self {
+            RankedStatus::Ranked | RankedStatus::Approved => "#b3ff66",
+            RankedStatus::Qualified => "#66ccff",
+            RankedStatus::Loved => "#ff66ab",
+            RankedStatus::Pending => "#ffd966",
+            RankedStatus::Graveyard | RankedStatus::Unsubmitted | RankedStatus::Unknown => {
+                "#cccccc"
+            }
+        }
+    }
+
+    pub fn is_ranked_or_loved(self) -> bool {
+        matches!(
+            self,
+            RankedStatus::Ranked | RankedStatus::Approved | RankedStatus::Loved
+        )
+    }
+}
+
+/// 最近一次读取osu!.db得到的谱面状态，键为谱面hash，供实时模式和记录文件中的成绩使用
+static BEATMAP_STATUS: LazyLock<Mutex<HashMap<String, RankedStatus>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub fn beatmap_status(hash: &str) -> RankedStatus {
+    BEATMAP_STATUS
+        .lock()
+        .unwrap()
+        .get(hash)
+        .copied()
+        .unwrap_or_default()
+}
+
+pub(crate) fn update_beatmap_status(statuses: HashMap<String, RankedStatus>) {
+    *BEATMAP_STATUS.lock().unwrap() = statuses;
+}
+
+/// 开启"只计入Ranked/Loved谱面"时，其他状态的谱面不进入玩家的成绩表和B30
+pub fn counts_for_b30(info: &RatingInfo) -> bool {
+    !SETTINGS.lock().unwrap().b30_ranked_only || info.map_info.status.is_ranked_or_loved()
+}
+
+/// 读取设置中的镜像文件，返回谱面hash到状态的覆盖表。没有设置镜像文件时返回空表。
+/// 镜像文件是用户选择的，读取失败或有无法识别的行时提示用户
+pub(crate) fn load_status_mirror() -> HashMap<String, RankedStatus> {
+    let Some(path) = SETTINGS.lock().unwrap().status_mirror.clone() else {
+        return HashMap::new();
+    };
+    match fs::read_to_string(&path).and_then(|content| parse_status_mirror(&content)) {
+        Ok((overrides, malformed)) => {
+            log::info!(
+                "Loaded {} status overrides from {}",
+                overrides.len(),
+                path.display()
+            );
+            if malformed > 0 {
+                notify(
+                    Level::Warn,
+                    tr!(
+                        "谱面状态镜像中有{}行无法识别，已忽略: {}",
+                        malformed,
+                        path.display()
+                    ),
+                );
+            }
+            overrides
+        }
+        Err(e) => {
+            notify(
+                Level::Warn,
+                tr!(
+                    "无法读取谱面状态镜像，使用osu!.db中的状态: {} ({})",
+                    e,
+                    path.display()
+                ),
+            );
+            HashMap::new()
+        }
+    }
+}
+
+/// 镜像文件为带表头的CSV，hash列名为checksum、file_md5、md5或hash，
+/// 状态列名为approved、status或ranked_status，其他列忽略。
+/// 返回覆盖表和无法识别的行数（缺少列、hash为空或状态无法识别）
+fn parse_status_mirror(content: &str) -> io::Result<(HashMap<String, RankedStatus>, usize)> {
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    let header = split_csv_line(lines.next().unwrap_or_default());
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|h| names.contains(&h.trim().to_ascii_lowercase().as_str()))
+    };
+    let (Some(hash_col), Some(status_col)) = (
+        column(&["checksum", "file_md5", "md5", "hash"]),
+        column(&["approved", "status", "ranked_status"]),
+    ) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            tr!("缺少谱面hash或状态列"),
+        ));
+    };
+
+    let mut overrides = HashMap::new();
+    let mut malformed = 0;
+    for line in lines {
+        let fields = split_csv_line(line);
+        if let (Some(hash), Some(status)) = (fields.get(hash_col), fields.get(status_col))
+            && !hash.trim().is_empty()
+            && let Some(status) = RankedStatus::parse(status)
+        {
+            overrides.insert(hash.trim().to_ascii_lowercase(), status);
+        } else {
+            malformed += 1;
+        }
+    }
+    Ok((overrides, malformed))
+}
+
+/// 按逗号分割一行CSV，支持用双引号包裹含逗号的字段
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_numeric_and_named_statuses() {
+        assert_eq!(RankedStatus::parse("1"), Some(RankedStatus::Ranked));
+        assert_eq!(RankedStatus::parse(" Loved "), Some(RankedStatus::Loved));
+        assert_eq!(RankedStatus::parse("-2"), Some(RankedStatus::Graveyard));
+        assert_eq!(RankedStatus::parse("WIP"), Some(RankedStatus::Pending));
+        assert_eq!(RankedStatus::parse("ranked-ish"), None);
+        assert_eq!(RankedStatus::parse(""), None);
+    }
+
+    #[test]
+    fn splits_quoted_fields() {
+        assert_eq!(
+            split_csv_line(r#"abc,"Title, with comma","say ""hi""",1"#),
+            vec!["abc", "Title, with comma", r#"say "hi""#, "1"]
+        );
+        assert_eq!(split_csv_line("a,,b"), vec!["a", "", "b"]);
+    }
+
+    #[test]
+    fn parses_mirror_and_counts_malformed_rows() {
+        let content = "\
+title,Checksum,approved
+\"Song, TV Size\",ABCDEF0123,1
+plain,0123abcdef,loved
+
+missing hash,,1
+unknown status,fedcba,ranked-ish
+short row
+";
+        let (overrides, malformed) = parse_status_mirror(content).unwrap();
+        assert_eq!(overrides.len(), 2);
+        assert_eq!(overrides["abcdef0123"], RankedStatus::Ranked);
+        assert_eq!(overrides["0123abcdef"], RankedStatus::Loved);
+        assert_eq!(malformed, 3);
+    }
+
+    #[test]
+    fn rejects_mirror_without_required_columns() {
+        assert!(parse_status_mirror("checksum,title\nabc,song\n").is_err());
+        assert!(parse_status_mirror("").is_err());
+    }
+}
@@ -0,0 +1,28 @@
+use crate::tr;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// 程序中可以恢复的错误，需要记录日志并告知用户
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{}", tr!("无法读取谱面文件 {}: {}", .path.display(), .reason))]
+    Beatmap { path: PathBuf, reason: String },
+    #[error("{}", tr!("模板 {} 加载失败: {}", .path, .reason))]
+    Template { path: &'static str, reason: String },
+    #[error("{}", tr!("图片渲染失败: {}", .0))]
+    Render(String),
+    #[error("{}", tr!("剪贴板不可用: {}", .0))]
+    Clipboard(#[from] arboard::Error),
+    #[error("{}", tr!("对话框打开失败: {}", .0))]
+    Dialog(#[from] native_dialog::Error),
+    #[error("{}", tr!("后台任务异常退出: {}", .0))]
+    Task(#[from] tokio::task::JoinError),
+    #[error("{}", tr!("无法验证导出文件: {}", .0))]
+    Verify(String),
+    #[error("{}", tr!("窗口已关闭"))]
+    WindowClosed(#[from] slint::EventLoopError),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
@@ -1,2 +1,11 @@
+mod card_cache;
+mod metadata;
+mod pdf;
 mod rating_generation;
-pub use rating_generation::{export_info, generate_single_card_pixmap};
+mod renderer;
+pub use metadata::read_manifest;
+pub use pdf::{ExportFormat, export_format, export_pdf};
+pub use rating_generation::{
+    export_info, export_pages, export_session_summary, format_bpm_str, format_length_str,
+    generate_single_card_pixmap,
+};
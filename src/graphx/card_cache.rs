@@ -0,0 +1,214 @@
+use lru::LruCache;
+use resvg::tiny_skia::Pixmap;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::db::{RankedStatus, RatingInfo, algorithm_stamp};
+use crate::i18n::current_language;
+
+/// 内存中最多缓存的卡片数量，卡片以PNG保存，每张约几百KB
+const CARD_CACHE_SIZE: usize = 300;
+const DISK_CACHE_DIR: &str = "cache/cards";
+/// 设为1时把卡片同时缓存到磁盘，重新启动程序后也不需要重新渲染
+const DISK_CACHE_ENV: &str = "MANIA_RATING_DISK_CACHE";
+/// 磁盘缓存的总大小上限，超过时删除最久没有使用的卡片
+const DISK_CACHE_MAX_BYTES: u64 = 256 * 1024 * 1024;
+/// 每写入这么多张卡片检查一次磁盘缓存的大小
+const DISK_PRUNE_INTERVAL: usize = 64;
+
+/// 全局共享的卡片缓存
+pub static CARD_CACHE: LazyLock<CardCache> = LazyLock::new(CardCache::new);
+
+/// 卡片缓存的键。卡片内容只取决于成绩、谱面状态、排名、模板和缩放
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CardKey {
+    pub beatmap_hash: String,
+    pub timestamp: i64,
+    pub player: String,
+    /// 卡片上的状态徽章，镜像文件更新后状态可能变化
+    pub status: RankedStatus,
+    pub rank: usize,
+    /// 模板路径、修改时间、语言和算法版本，修改模板、切换语言或更新算法后旧的缓存自然失效
+    pub theme: String,
+    /// 缩放的百分比
+    pub scale: u32,
+}
+
+impl CardKey {
+    pub fn new(rank: usize, info: &RatingInfo, template_path: &str, scale: u32) -> Self {
+        CardKey {
+            beatmap_hash: info.map_info.hash.clone(),
+            timestamp: info.score_info.timestamp.timestamp_millis(),
+            player: info.score_info.player.clone(),
+            status: info.map_info.status,
+            rank,
+            theme: theme_id(template_path),
+            scale,
+        }
+    }
+
+    /// 磁盘缓存的文件名。使用SHA-256而不是`DefaultHasher`，后者的结果在不同的Rust版本之间可能变化
+    fn file_name(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!(
+            "{}|{}|{}|{:?}|{}|{}|{}",
+            self.beatmap_hash,
+            self.timestamp,
+            self.player,
+            self.status,
+            self.rank,
+            self.theme,
+            self.scale
+        ));
+        let digest = format!("{:x}", hasher.finalize());
+        format!("{}-{}.png", self.beatmap_hash, &digest[..32])
+    }
+}
+
+fn theme_id(template_path: &str) -> String {
+    let modified = fs::metadata(template_path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format!(
+        "{template_path}@{modified}@{}@{}",
+        current_language().code(),
+        algorithm_stamp()
+    )
+}
+
+/// 渲染好的卡片缓存：内存中的LRU缓存，以及可选的磁盘缓存
+pub struct CardCache {
+    memory: Mutex<LruCache<CardKey, Arc<Vec<u8>>>>,
+    disk_dir: Option<PathBuf>,
+    /// 上次清理之后写入磁盘的卡片数量
+    disk_writes: AtomicUsize,
+}
+
+impl CardCache {
+    fn new() -> Self {
+        let disk_dir = match env::var(DISK_CACHE_ENV).as_deref() {
+            Ok("1") | Ok("true") => env::current_dir()
+                .ok()
+                .map(|dir| dir.join(DISK_CACHE_DIR))
+                .filter(|dir| match fs::create_dir_all(dir) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        log::warn!("Failed to create card cache {}: {e}", dir.display());
+                        false
+                    }
+                }),
+            _ => None,
+        };
+        if let Some(dir) = &disk_dir {
+            prune_disk_cache(dir);
+        }
+        CardCache {
+            memory: Mutex::new(LruCache::new(NonZeroUsize::new(CARD_CACHE_SIZE).unwrap())),
+            disk_dir,
+            disk_writes: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &CardKey) -> Option<Pixmap> {
+        let cached = self.memory.lock().unwrap().get(key).cloned();
+        let png = match cached {
+            Some(png) => png,
+            None => {
+                let path = self.disk_path(key)?;
+                let png = Arc::new(fs::read(&path).ok()?);
+                // 修改时间作为最近使用的时间，清理时先删除最久没有使用的卡片
+                let _ = File::options()
+                    .write(true)
+                    .open(&path)
+                    .and_then(|file| file.set_modified(SystemTime::now()));
+                self.memory.lock().unwrap().put(key.clone(), png.clone());
+                png
+            }
+        };
+        match Pixmap::decode_png(&png) {
+            Ok(pixmap) => Some(pixmap),
+            Err(e) => {
+                log::warn!("Failed to decode cached card: {e}");
+                self.memory.lock().unwrap().pop(key);
+                None
+            }
+        }
+    }
+
+    pub fn put(&self, key: CardKey, pixmap: &Pixmap) {
+        let png = match pixmap.encode_png() {
+            Ok(png) => png,
+            Err(e) => {
+                log::warn!("Failed to encode card for cache: {e}");
+                return;
+            }
+        };
+        if let Some(path) = self.disk_path(&key) {
+            match fs::write(&path, &png) {
+                Ok(()) => {
+                    if self.disk_writes.fetch_add(1, Ordering::Relaxed) + 1 >= DISK_PRUNE_INTERVAL
+                        && let Some(dir) = &self.disk_dir
+                    {
+                        self.disk_writes.store(0, Ordering::Relaxed);
+                        prune_disk_cache(dir);
+                    }
+                }
+                Err(e) => log::warn!("Failed to write card cache {}: {e}", path.display()),
+            }
+        }
+        self.memory.lock().unwrap().put(key, Arc::new(png));
+    }
+
+    fn disk_path(&self, key: &CardKey) -> Option<PathBuf> {
+        self.disk_dir.as_ref().map(|dir| dir.join(key.file_name()))
+    }
+}
+
+/// 磁盘缓存超过大小上限时，按修改时间从旧到新删除卡片
+fn prune_disk_cache(dir: &Path) {
+    if let Err(e) = prune_dir(dir, DISK_CACHE_MAX_BYTES) {
+        log::warn!("Failed to prune card cache {}: {e}", dir.display());
+    }
+}
+
+fn prune_dir(dir: &Path, max_bytes: u64) -> io::Result<()> {
+    let mut files = fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            metadata.is_file().then(|| {
+                (
+                    metadata.modified().unwrap_or(UNIX_EPOCH),
+                    metadata.len(),
+                    entry.path(),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut total = files.iter().map(|(_, len, _)| len).sum::<u64>();
+    if total <= max_bytes {
+        return Ok(());
+    }
+    files.sort_unstable_by_key(|(modified, _, _)| *modified);
+    let mut removed = 0;
+    for (_, len, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        fs::remove_file(&path)?;
+        total -= len;
+        removed += 1;
+    }
+    log::info!("Removed {removed} cards from the disk cache");
+    Ok(())
+}
@@ -0,0 +1,202 @@
+use base64::prelude::*;
+use std::fs;
+use std::path::Path;
+
+use crate::db::ExportManifest;
+use crate::error::{AppError, AppResult};
+use crate::tr;
+
+/// 清单所在的JPEG注释段以此开头，清单较大时分成多段
+const COMMENT_PREFIX: &[u8] = b"mania-rating-gui:";
+/// 一个段的最大长度（包括长度字段本身）
+const MAX_SEGMENT_LEN: usize = 0xFFFF;
+const MARKER_SOI: u8 = 0xD8;
+const MARKER_SOS: u8 = 0xDA;
+const MARKER_APP0: u8 = 0xE0;
+const MARKER_COM: u8 = 0xFE;
+/// PDF文档信息字典中保存清单的键，值为清单JSON的Base64，只包含ASCII字符，不需要转义
+pub(super) const PDF_MANIFEST_KEY: &[u8] = b"ManiaRatingManifest";
+
+/// 把清单以注释段（COM）写入JPEG，放在JFIF头之后
+pub(super) fn embed_manifest(jpeg: &[u8], manifest: &ExportManifest) -> Vec<u8> {
+    let mut insert_at = 2;
+    if jpeg.get(2..4) == Some(&[0xFF, MARKER_APP0])
+        && let Some(len) = jpeg.get(4..6)
+    {
+        insert_at = 4 + u16::from_be_bytes([len[0], len[1]]) as usize;
+    }
+    let json = manifest.to_json();
+    let mut output = Vec::with_capacity(jpeg.len() + json.len() + 64);
+    output.extend_from_slice(&jpeg[..insert_at]);
+    for chunk in json
+        .as_bytes()
+        .chunks(MAX_SEGMENT_LEN - 2 - COMMENT_PREFIX.len())
+    {
+        let len = (2 + COMMENT_PREFIX.len() + chunk.len()) as u16;
+        output.extend_from_slice(&[0xFF, MARKER_COM]);
+        output.extend_from_slice(&len.to_be_bytes());
+        output.extend_from_slice(COMMENT_PREFIX);
+        output.extend_from_slice(chunk);
+    }
+    output.extend_from_slice(&jpeg[insert_at..]);
+    output
+}
+
+/// PDF中保存的清单
+pub(super) fn pdf_manifest_value(manifest: &ExportManifest) -> String {
+    BASE64_STANDARD.encode(manifest.to_json())
+}
+
+/// 读取导出图片或PDF中的清单
+pub fn read_manifest(path: &Path) -> AppResult<ExportManifest> {
+    let data = fs::read(path)?;
+    let json = if data.starts_with(b"%PDF") {
+        pdf_manifest_json(&data)?
+    } else if data.get(0..2) == Some(&[0xFF, MARKER_SOI]) {
+        jpeg_manifest_json(&data)?
+    } else {
+        return Err(AppError::Verify(tr!("不是导出的JPEG图片或PDF")));
+    };
+    if json.is_empty() {
+        return Err(AppError::Verify(tr!("文件中没有成绩清单")));
+    }
+    let json = String::from_utf8(json).map_err(|e| AppError::Verify(e.to_string()))?;
+    ExportManifest::from_json(&json).map_err(|e| AppError::Verify(e.to_string()))
+}
+
+fn jpeg_manifest_json(jpeg: &[u8]) -> AppResult<Vec<u8>> {
+    let mut json = Vec::new();
+    let mut pos = 2;
+    // 逐段读取，到图像数据为止
+    while let Some(&[0xFF, marker, high, low]) = jpeg.get(pos..pos + 4) {
+        if marker == MARKER_SOS {
+            break;
+        }
+        let len = u16::from_be_bytes([high, low]) as usize;
+        let Some(data) = jpeg.get(pos + 4..pos + 2 + len) else {
+            break;
+        };
+        if marker == MARKER_COM
+            && let Some(chunk) = data.strip_prefix(COMMENT_PREFIX)
+        {
+            json.extend_from_slice(chunk);
+        }
+        pos += 2 + len;
+    }
+    Ok(json)
+}
+
+/// 在文档信息字典中查找`/ManiaRatingManifest (...)`，增量保存后取最后一个
+fn pdf_manifest_json(pdf: &[u8]) -> AppResult<Vec<u8>> {
+    let key = [b"/", PDF_MANIFEST_KEY, b" ("].concat();
+    let Some(start) = pdf
+        .windows(key.len())
+        .rposition(|window| window == key.as_slice())
+        .map(|pos| pos + key.len())
+    else {
+        return Ok(Vec::new());
+    };
+    let Some(len) = pdf[start..].iter().position(|&b| b == b')') else {
+        return Ok(Vec::new());
+    };
+    BASE64_STANDARD
+        .decode(&pdf[start..start + len])
+        .map_err(|e| AppError::Verify(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphx::pdf::pages_to_pdf;
+    use serde_json::json;
+
+    /// 只有JFIF头和一个空的扫描段的JPEG，足够测试注释段的写入和读取
+    const JPEG: &[u8] = &[
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00, 0x01, 0x01, 0x00, 0x00,
+        0x01, 0x00, 0x01, 0x00, 0x00, 0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9,
+    ];
+
+    fn manifest(card_count: usize) -> ExportManifest {
+        let cards = (0..card_count)
+            .map(|i| {
+                json!({
+                    "rank": i + 1,
+                    "player": "tester",
+                    "map_hash": format!("{i:032x}"),
+                    "mods": 0,
+                    "judgements": [900, 80, 15, 3, 1, 1],
+                    "accuracy": 97.5,
+                    "accuracy_rating": 96.25,
+                    "timestamp": "2025-07-25T12:00:00Z",
+                    "diff_const": 12.3456,
+                    "rating": 11.2345,
+                })
+            })
+            .collect::<Vec<_>>();
+        let manifest = json!({
+            "format": 1,
+            "app_version": "0.2.0",
+            "algorithm": "test",
+            "generated_at": "2025-07-25T12:30:00Z",
+            "player": "tester",
+            "cards": cards,
+            "content_hash": "0123",
+        });
+        ExportManifest::from_json(&manifest.to_string()).unwrap()
+    }
+
+    fn parse(json: Vec<u8>) -> ExportManifest {
+        ExportManifest::from_json(&String::from_utf8(json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn jpeg_round_trip() {
+        for card_count in [0, 3] {
+            let manifest = manifest(card_count);
+            let jpeg = embed_manifest(JPEG, &manifest);
+            // 注释段在JFIF头之后，原有的数据保持不变
+            assert_eq!(jpeg[..20], JPEG[..20]);
+            assert_eq!(jpeg[20..22], [0xFF, MARKER_COM]);
+            assert!(jpeg.ends_with(&JPEG[20..]));
+            let parsed = parse(jpeg_manifest_json(&jpeg).unwrap());
+            assert_eq!(parsed.to_json(), manifest.to_json());
+        }
+    }
+
+    #[test]
+    fn jpeg_round_trip_across_segments() {
+        let manifest = manifest(1000);
+        assert!(manifest.to_json().len() > MAX_SEGMENT_LEN);
+        let jpeg = embed_manifest(JPEG, &manifest);
+        let parsed = parse(jpeg_manifest_json(&jpeg).unwrap());
+        assert_eq!(parsed.to_json(), manifest.to_json());
+    }
+
+    #[test]
+    fn pdf_round_trip() {
+        let manifest = manifest(3);
+        let pdf = pages_to_pdf(&[], &manifest).unwrap();
+        let parsed = parse(pdf_manifest_json(&pdf).unwrap());
+        assert_eq!(parsed.to_json(), manifest.to_json());
+    }
+
+    #[test]
+    fn read_manifest_from_files() {
+        let manifest = manifest(2);
+        let dir =
+            std::env::temp_dir().join(format!("mania-rating-gui-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let jpeg_path = dir.join("export.jpg");
+        let pdf_path = dir.join("export.pdf");
+        fs::write(&jpeg_path, embed_manifest(JPEG, &manifest)).unwrap();
+        fs::write(&pdf_path, pages_to_pdf(&[], &manifest).unwrap()).unwrap();
+
+        for path in [&jpeg_path, &pdf_path] {
+            assert_eq!(read_manifest(path).unwrap().to_json(), manifest.to_json());
+        }
+        // 没有清单的JPEG
+        fs::write(&jpeg_path, JPEG).unwrap();
+        assert!(read_manifest(&jpeg_path).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
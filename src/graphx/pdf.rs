@@ -0,0 +1,238 @@
+use base64::prelude::*;
+use chrono::Local;
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+use rayon::prelude::*;
+use resvg::usvg;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use super::metadata::{PDF_MANIFEST_KEY, pdf_manifest_value};
+use super::rating_generation::{
+    export_dir, export_page_svg, generate_export_data, generate_single_card_svg,
+};
+use super::renderer::RENDERER;
+use crate::db::{ExportManifest, RatingInfo};
+use crate::error::{AppError, AppResult};
+use crate::i18n::template_strings;
+use crate::settings::SETTINGS;
+use crate::tr;
+
+const APPENDIX_TEMPLATE_PATH: &str = "svg/appendix.svg";
+/// 附表每页的行数
+const APPENDIX_ROWS: usize = 40;
+const APPENDIX_ROW_HEIGHT: u32 = 80;
+/// 表头的下边缘，即第一行的上边缘
+const APPENDIX_TOP: u32 = 350;
+/// 附表中谱面名称的最大字符数
+const APPENDIX_TITLE_MAX_LEN: usize = 48;
+/// 1px对应的pt，3600px宽的页面为720pt（约25.4cm）
+const PT_PER_PX: f32 = 0.2;
+
+/// 导出的文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Jpeg,
+    /// 矢量的PDF，卡片后面附有所有成绩的数值表
+    Pdf,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 2] = [ExportFormat::Jpeg, ExportFormat::Pdf];
+
+    /// 设置中显示的名称
+    pub fn label(self) -> String {
+        match self {
+            ExportFormat::Jpeg => tr!("JPEG图片"),
+            ExportFormat::Pdf => tr!("PDF（矢量，附成绩表）"),
+        }
+    }
+}
+
+/// 当前设置的导出格式
+pub fn export_format() -> ExportFormat {
+    SETTINGS.lock().unwrap().export_format
+}
+
+#[derive(Serialize)]
+struct AppendixRow {
+    y: u32,
+    stripe: bool,
+    rank: usize,
+    map: String,
+    mods: String,
+    judgements: String,
+    acc: String,
+    acc_rating: String,
+    sr: String,
+    diff_const: String,
+    rating: String,
+    time: String,
+}
+
+/// 导出PDF：标题和卡片（每页最多`page_size`张），后面是所有成绩的数值附表。
+/// `cards`中的序号为卡片上显示的排名。`title`为None时只导出一页卡片，文件以玩家名命名。
+/// 标题中显示`average_rating`，由调用者决定按哪些成绩计算。
+/// 成绩清单保存在文档信息字典中，与JPEG一样可以验证
+pub fn export_pdf(
+    player_name: &str,
+    title: Option<&str>,
+    cards: &[(usize, RatingInfo)],
+    average_rating: f64,
+    page_size: usize,
+) -> AppResult<PathBuf> {
+    let page_size = if title.is_some() {
+        page_size.max(1)
+    } else {
+        cards.len().max(1)
+    };
+
+    let mut pages = Vec::new();
+    let card_page_count = cards.len().div_ceil(page_size);
+    for (page, chunk) in cards.chunks(page_size).enumerate() {
+        // 卡片以SVG嵌入，在PDF中保持矢量
+        let svg_cards = chunk
+            .par_iter()
+            .map(|(i, info)| Ok(BASE64_STANDARD.encode(generate_single_card_svg(*i, info)?)))
+            .collect::<AppResult<Vec<_>>>()?;
+        let page_label = title.map(|title| tr!("{} · 第{}/{}页", title, page + 1, card_page_count));
+        let svg_content = export_page_svg(
+            player_name,
+            generate_export_data(svg_cards, "image/svg+xml"),
+            average_rating,
+            page_label,
+        )?;
+        pages.push(RENDERER.parse_tree(&svg_content)?);
+    }
+
+    let appendix_page_count = cards.len().div_ceil(APPENDIX_ROWS);
+    for (page, chunk) in cards.chunks(APPENDIX_ROWS).enumerate() {
+        let rows = chunk
+            .iter()
+            .enumerate()
+            .map(|(row, (i, info))| appendix_row(row, *i, info))
+            .collect::<Vec<_>>();
+        let total_height = APPENDIX_TOP + (chunk.len() as u32 + 1) * APPENDIX_ROW_HEIGHT;
+        let page_label = tr!(
+            "{} · 第{}/{}页",
+            tr!("成绩附表"),
+            page + 1,
+            appendix_page_count
+        );
+        let svg_content = RENDERER.render_template(
+            APPENDIX_TEMPLATE_PATH,
+            &json!({
+                "total_height": total_height,
+                "player_name": player_name,
+                "page_label": page_label,
+                "rows": rows,
+                "t": template_strings(),
+            }),
+        )?;
+        pages.push(RENDERER.parse_tree(&svg_content)?);
+    }
+
+    let manifest = ExportManifest::new(player_name, cards.iter().map(|(i, info)| (*i, info)));
+    let pdf = pages_to_pdf(&pages, &manifest)?;
+    let file_name = match title {
+        Some(_) => format!("{player_name}-{}.pdf", Local::now().format("%Y%m%d-%H%M%S")),
+        None => format!("{player_name}.pdf"),
+    };
+    let pdf_path = export_dir()?.join(file_name);
+    fs::write(&pdf_path, pdf)?;
+    Ok(pdf_path)
+}
+
+fn appendix_row(row: usize, i: usize, info: &RatingInfo) -> AppendixRow {
+    let beatmap_info = &info.map_info.info;
+    let map = format!("{} [{}]", beatmap_info.title, beatmap_info.version);
+    let map = if utf8_slice::len(&map) > APPENDIX_TITLE_MAX_LEN {
+        format!("{}...", utf8_slice::till(&map, APPENDIX_TITLE_MAX_LEN - 3))
+    } else {
+        map
+    };
+    let mods = info.score_info.mods_text();
+    AppendixRow {
+        y: APPENDIX_TOP + row as u32 * APPENDIX_ROW_HEIGHT,
+        stripe: row % 2 == 1,
+        rank: i + 1,
+        map,
+        mods: if mods.is_empty() {
+            "-".to_string()
+        } else {
+            mods
+        },
+        judgements: info
+            .score_info
+            .judgement_num
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join("/"),
+        acc: format!("{:.02}%", info.score_info.accuracy),
+        acc_rating: format!("{:.02}%", info.score_info.accuracy_rating),
+        sr: format!("{:.02}", beatmap_info.sr.unwrap_or(0.0)),
+        diff_const: format!("{:.02}", info.diff_const),
+        rating: format!("{:.02}", info.rating),
+        time: info
+            .score_info
+            .timestamp
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+    }
+}
+
+/// 把每个SVG转换为PDF中的一页，保留矢量图形和文字
+pub(super) fn pages_to_pdf(pages: &[usvg::Tree], manifest: &ExportManifest) -> AppResult<Vec<u8>> {
+    let mut alloc = Ref::new(1);
+    let catalog_id = alloc.bump();
+    let page_tree_id = alloc.bump();
+    let info_id = alloc.bump();
+    let svg_name = Name(b"S1");
+    let mut pdf = Pdf::new();
+    let mut page_ids = Vec::new();
+
+    for tree in pages {
+        let (chunk, svg_id) = svg2pdf::to_chunk(tree, svg2pdf::ConversionOptions::default())
+            .map_err(|e| AppError::Render(e.to_string()))?;
+        // svg2pdf生成的对象从1开始编号，需要重新编号以免与其他页面冲突
+        let mut id_map = HashMap::new();
+        let chunk = chunk.renumber(|old| *id_map.entry(old).or_insert_with(|| alloc.bump()));
+        let svg_id = id_map[&svg_id];
+
+        let page_id = alloc.bump();
+        let content_id = alloc.bump();
+        let width = tree.size().width() * PT_PER_PX;
+        let height = tree.size().height() * PT_PER_PX;
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, width, height));
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        page.resources().x_objects().pair(svg_name, svg_id);
+        page.finish();
+
+        // 转换得到的XObject大小为1x1，缩放到整页
+        let mut content = Content::new();
+        content.save_state();
+        content.transform([width, 0.0, 0.0, height, 0.0, 0.0]);
+        content.x_object(svg_name);
+        content.restore_state();
+        pdf.stream(content_id, &content.finish());
+        pdf.extend(&chunk);
+        page_ids.push(page_id);
+    }
+
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    let manifest = pdf_manifest_value(manifest);
+    pdf.document_info(info_id)
+        .pair(Name(PDF_MANIFEST_KEY), Str(manifest.as_bytes()));
+    pdf.pages(page_tree_id)
+        .kids(page_ids.iter().copied())
+        .count(page_ids.len() as i32);
+    Ok(pdf.finish())
+}
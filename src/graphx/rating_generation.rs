@@ -1,37 +1,53 @@
+use base64::prelude::*;
 use chrono::Local;
-use handlebars::Handlebars;
-use resvg::{tiny_skia, usvg};
+use rayon::prelude::*;
+use resvg::tiny_skia;
 use serde_json::json;
 use std::{
-    env, fs, io,
+    collections::HashSet,
+    env, fs,
+    io::Cursor,
     path::{Path, PathBuf},
-    sync::{Arc, LazyLock},
+    sync::{Arc, LazyLock, Mutex},
 };
 
+use super::card_cache::{CARD_CACHE, CardKey};
+use super::metadata::embed_manifest;
+use super::renderer::{Background, RENDERER};
 use crate::ScoreTileBase64;
-use crate::db::RatingInfo;
+use crate::db::{
+    ExportManifest, RankedStatus, RatingInfo, SessionSummary, algorithm_label, overall_definition,
+};
+use crate::error::{AppError, AppResult};
+use crate::i18n::template_strings;
+use crate::tr;
+use crate::ui::notify::notify;
 
 const INFO_CARD_TEMPLATE_PATH: &str = "svg/rating_single.svg";
 const EXPORT_TEMPLATE_PATH: &str = "svg/export.svg";
+const SESSION_TEMPLATE_PATH: &str = "svg/session.svg";
+const SESSION_WIDTH: u32 = 1200;
+const SESSION_HEIGHT: u32 = 900;
 const NO_IMAGE_PATH: &str = "svg/no_image.jpg";
-const FONT_DIR_PATH: &str = "fonts";
 const CARD_HEIGHT: u32 = 350;
 const CARD_WIDTH: u32 = 1200;
+/// 导出页面的宽度，3列卡片
+const EXPORT_WIDTH: u32 = 3 * CARD_WIDTH;
+/// 卡片的缩放百分比，目前只有原始大小
+const CARD_SCALE: u32 = 100;
 const PERIMETER: f64 = 628.3185307179586;
 
 const TITLE_MAX_LEN: usize = 28;
 const VERSION_MAX_LEN: usize = 55;
 
-static FONT_ARC: LazyLock<Arc<usvg::fontdb::Database>> = LazyLock::new(|| {
-    let mut fontdb = usvg::fontdb::Database::new();
-    fontdb.load_fonts_dir(FONT_DIR_PATH);
-    fontdb.load_system_fonts();
-    Arc::new(fontdb)
-});
+/// 已经提示过缺失或无法读取的背景图片，每个只提示一次
+static MISSING_BACKGROUNDS: LazyLock<Mutex<HashSet<PathBuf>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
 
 #[derive(serde::Serialize)]
 struct CardData {
     bg_image: String,
+    bg_blurred: String,
     title_ascii: String,
     title: String,
     artist_ascii: String,
@@ -79,30 +95,49 @@ struct CardData {
     beatmap_hash: String,
     beatmap_url: String,
     status: String,
+    status_color: &'static str,
+    status_width: u32,
 }
 
 #[derive(serde::Serialize)]
-struct ExportCardData {
+pub(super) struct ExportCardData {
     x_offset: u32,
     y_offset: u32,
+    /// 图片导出时为PNG，PDF导出时为矢量的SVG
+    mime: &'static str,
     base64_data: String,
 }
 
-fn generate_card_cata(i: usize, info: &RatingInfo) -> CardData {
-    let beatmap_info = &info.map_info.info;
-    let bg_name = match &beatmap_info.bg_name {
+/// 读取谱面的背景图片，找不到或无法读取时使用默认背景
+fn load_card_background(info: &RatingInfo) -> AppResult<Arc<Background>> {
+    let bg_name = match &info.map_info.info.bg_name {
         Some(s) => s.as_str(),
         None => "",
     };
-    let osu_file_dir = info.map_info.path.parent().unwrap();
+    let osu_file_dir = info.map_info.path.parent().unwrap_or(Path::new(""));
     let bg_path = osu_file_dir.join(Path::new(bg_name));
-    let default_path = env::current_dir().unwrap().join(Path::new(NO_IMAGE_PATH));
-    let final_path = if bg_path.exists() {
-        bg_path
+    let default_path = env::current_dir()
+        .unwrap_or_default()
+        .join(Path::new(NO_IMAGE_PATH));
+    if bg_name.is_empty() {
+        return RENDERER.background(&default_path);
+    }
+    let message = if bg_path.is_file() {
+        match RENDERER.background(&bg_path) {
+            Ok(background) => return Ok(background),
+            Err(e) => tr!("{}，使用默认背景", e),
+        }
     } else {
-        default_path
+        tr!("找不到背景图片 {}，使用默认背景", bg_path.display())
     };
-    let bg_path_string = final_path.to_string_lossy().into_owned().replace("\\", "/");
+    if MISSING_BACKGROUNDS.lock().unwrap().insert(bg_path) {
+        notify(log::Level::Warn, message);
+    }
+    RENDERER.background(&default_path)
+}
+
+fn generate_card_cata(i: usize, info: &RatingInfo, background: &Background) -> CardData {
+    let beatmap_info = &info.map_info.info;
 
     let title = beatmap_info
         .title_unicode
@@ -187,17 +222,24 @@ fn generate_card_cata(i: usize, info: &RatingInfo) -> CardData {
     let beatmap_hash = info.map_info.hash.clone();
     let (beatmap_url, status) = if beatmap_info.beatmap_set_id == -1 || beatmap_info.beatmap_id == 0
     {
-        (String::new(), "Unsubmitted")
+        (String::new(), RankedStatus::Unsubmitted)
     } else {
-        // 需要有一个方法验证beatmap在官网的状态，暂时无法实现
+        // 状态来自osu!.db，或者设置中的镜像文件
         (
             format!(
                 "/beatmapsets/{}#mania/{}",
                 beatmap_info.beatmap_set_id, beatmap_info.beatmap_id
             ),
-            "",
+            info.map_info.status,
         )
     };
+    let status_label = status.label();
+    // 徽章宽度按字符估算，全角字符更宽
+    let status_width = status_label
+        .chars()
+        .map(|c| if c.is_ascii() { 12 } else { 20 })
+        .sum::<u32>()
+        + 24;
 
     let mods = info.score_info.mods;
     let is_score_v2 = mods.bits() & 0x2000_0000 != 0;
@@ -252,7 +294,8 @@ fn generate_card_cata(i: usize, info: &RatingInfo) -> CardData {
     };
 
     CardData {
-        bg_image: bg_path_string,
+        bg_image: background.cover.clone(),
+        bg_blurred: background.blurred.clone(),
         title_ascii: title_ascii.into(),
         title,
         artist_ascii: artist_ascii.into(),
@@ -308,128 +351,268 @@ fn generate_card_cata(i: usize, info: &RatingInfo) -> CardData {
             .to_string(),
         beatmap_hash,
         beatmap_url,
-        status: status.into(),
+        status: status_label,
+        status_color: status.color(),
+        status_width,
     }
 }
 
-fn generate_export_data(info_vec: Vec<ScoreTileBase64>) -> Vec<ExportCardData> {
-    info_vec
+/// 按3列排列卡片，`base64_cards`为base64编码的卡片图像
+pub(super) fn generate_export_data(
+    base64_cards: Vec<String>,
+    mime: &'static str,
+) -> Vec<ExportCardData> {
+    base64_cards
         .into_iter()
         .enumerate()
-        .map(|(i, info)| ExportCardData {
+        .map(|(i, base64_data)| ExportCardData {
             x_offset: i as u32 % 3 * CARD_WIDTH,
             y_offset: (i as u32 / 3 + 1) * CARD_HEIGHT,
-            base64_data: info.base64_string,
+            mime,
+            base64_data,
         })
         .collect()
 }
 
+fn png_export_data(info_vec: Vec<ScoreTileBase64>) -> Vec<ExportCardData> {
+    generate_export_data(
+        info_vec
+            .into_iter()
+            .map(|info| info.base64_string)
+            .collect(),
+        "image/png",
+    )
+}
+
+/// 导出成绩表，`manifest`为导出卡片的成绩清单，写入图片中用于验证
 pub fn export_info(
     player_name: &str,
     info_vec: Vec<ScoreTileBase64>,
     average_rating: f64,
-) -> io::Result<PathBuf> {
-    let y_disclaimer = ((info_vec.len() as f64 / 3.0).ceil() as u32 + 1) * CARD_HEIGHT;
-    let total_height = y_disclaimer + 150;
+    manifest: &ExportManifest,
+) -> AppResult<PathBuf> {
+    let pixmap = render_export_page(player_name, png_export_data(info_vec), average_rating, None)?;
+
+    // 保存为PNG
+    // let santized_name = sanitize_filename(&info_vec[0].score_info.player);
+    let pic_path = export_dir()?.join(format!("{player_name}.jpg"));
+    save_jpeg(pixmap, &pic_path, manifest)?;
+    Ok(pic_path)
+}
+
+/// 分页导出一组成绩，每页最多`page_size`张卡片，卡片按传入的顺序编号。
+/// 标题中显示`average_rating`，由调用者决定按哪些成绩计算。
+/// 页面保存在export下单独的文件夹中，返回该文件夹
+pub fn export_pages(
+    player_name: &str,
+    title: &str,
+    ratings: &[RatingInfo],
+    average_rating: f64,
+    page_size: usize,
+) -> AppResult<PathBuf> {
+    let page_size = page_size.max(1);
+    let page_count = ratings.len().div_ceil(page_size);
+    let page_dir = export_dir()?.join(format!(
+        "{player_name}-{}",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    fs::create_dir_all(&page_dir)?;
+
+    for (page, chunk) in ratings.chunks(page_size).enumerate() {
+        let offset = page * page_size;
+        // 逐页生成卡片，避免同时保存所有卡片的图像
+        let info_vec = chunk
+            .par_iter()
+            .enumerate()
+            .map(|(i, info)| {
+                let png = generate_single_card_pixmap(offset + i, info)?
+                    .encode_png()
+                    .map_err(|e| AppError::Render(e.to_string()))?;
+                Ok(ScoreTileBase64 {
+                    index: (offset + i) as i32,
+                    base64_string: BASE64_STANDARD.encode(png),
+                })
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+        let page_label = tr!("{} · 第{}/{}页", title, page + 1, page_count);
+        let manifest = ExportManifest::new(
+            player_name,
+            chunk.iter().enumerate().map(|(i, info)| (offset + i, info)),
+        );
+        let pixmap = render_export_page(
+            player_name,
+            png_export_data(info_vec),
+            average_rating,
+            Some(page_label),
+        )?;
+        save_jpeg(
+            pixmap,
+            &page_dir.join(format!("page-{:02}.jpg", page + 1)),
+            &manifest,
+        )?;
+    }
+    Ok(page_dir)
+}
+
+fn render_export_page(
+    player_name: &str,
+    cards: Vec<ExportCardData>,
+    average_rating: f64,
+    page_label: Option<String>,
+) -> AppResult<tiny_skia::Pixmap> {
+    let (_, total_height) = export_page_height(cards.len());
+    let svg_content = export_page_svg(player_name, cards, average_rating, page_label)?;
+    // 解析并渲染SVG
+    RENDERER.render_pixmap(&svg_content, EXPORT_WIDTH, total_height)
+}
+
+/// 导出页面中免责声明的位置和页面的总高度
+fn export_page_height(card_count: usize) -> (u32, u32) {
+    let y_disclaimer = ((card_count as f64 / 3.0).ceil() as u32 + 1) * CARD_HEIGHT;
+    (y_disclaimer, y_disclaimer + 190)
+}
+
+/// 按导出模板生成一页：标题、3列的卡片和免责声明。分页导出时页眉和页脚显示页码
+pub(super) fn export_page_svg(
+    player_name: &str,
+    cards: Vec<ExportCardData>,
+    average_rating: f64,
+    page_label: Option<String>,
+) -> AppResult<String> {
+    let (y_disclaimer, total_height) = export_page_height(cards.len());
     let player_name_f = if player_name == "[Recent 30]" {
-        "Recent 30".into()
+        tr!("Recent 30")
     } else {
-        format!("Player: {player_name}")
+        tr!("Player: {}", player_name)
     };
     let average_rating_fill = format_diff_gradient(average_rating);
     let average_rating = format!("{average_rating:.02}");
+    let overall_label = format!("{}: ", overall_definition().short_label());
     let generated_time = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    let cards = generate_export_data(info_vec);
-
-    let mut reg = Handlebars::new();
-    reg.register_template_file("template", EXPORT_TEMPLATE_PATH)
-        .expect("Failed to register template");
-    let svg_content = reg
-        .render(
-            "template",
-            &json!({
-                "total_height": total_height,
-                "player_name": player_name_f,
-                "average_rating_fill": average_rating_fill,
-                "average_rating": average_rating,
-                "generated_time": generated_time,
-                "cards": cards,
-                "y_disclaimer": y_disclaimer
-            }),
-        )
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-
-    let options = usvg::Options {
-        fontdb: FONT_ARC.clone(),
-        ..Default::default()
-    };
-
-    // 解析并渲染SVG
-    let tree = usvg::Tree::from_str(&svg_content, &options)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-    let mut pixmap = tiny_skia::Pixmap::new(3600, total_height)
-        .ok_or_else(|| io::Error::other("Failed to create pixmap"))?;
-
-    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
-
-    // 确保输出目录存在
-    let save_pic_path = env::current_dir().unwrap().join("export");
-    if let Some(parent) = save_pic_path.parent() {
-        fs::create_dir_all(parent)?;
-    }
-
-    // 保存为PNG
-    // let santized_name = sanitize_filename(&info_vec[0].score_info.player);
-    let pic_name = format!("{player_name}.jpg");
+    RENDERER.render_template(
+        EXPORT_TEMPLATE_PATH,
+        &json!({
+            "total_height": total_height,
+            "player_name": player_name_f,
+            "overall_label": overall_label,
+            "average_rating_fill": average_rating_fill,
+            "average_rating": average_rating,
+            "generated_time": generated_time,
+            "page_label": page_label,
+            "algorithm": algorithm_label(),
+            "cards": cards,
+            "t": template_strings(),
+            "y_disclaimer": y_disclaimer
+        }),
+    )
+}
 
-    if !save_pic_path.exists() {
-        fs::create_dir_all(&save_pic_path)?;
-    }
-    let pic_path = save_pic_path.join(pic_name);
+/// 导出目录，不存在时创建
+pub(super) fn export_dir() -> AppResult<PathBuf> {
+    let save_pic_path = env::current_dir()?.join("export");
+    fs::create_dir_all(&save_pic_path)?;
+    Ok(save_pic_path)
+}
 
-    let image = image::RgbaImage::from_raw(pixmap.width(), pixmap.height(), pixmap.take()).unwrap();
+/// 保存为JPEG，并写入成绩清单
+fn save_jpeg(
+    pixmap: tiny_skia::Pixmap,
+    pic_path: &Path,
+    manifest: &ExportManifest,
+) -> AppResult<()> {
+    let image = image::RgbaImage::from_raw(pixmap.width(), pixmap.height(), pixmap.take())
+        .ok_or_else(|| AppError::Render(tr!("无法转换画布")))?;
 
     // Rgba8不支持导出到Jpeg
     let rgb_image = image::DynamicImage::ImageRgba8(image).to_rgb8();
+    let mut jpeg = Vec::new();
     rgb_image
-        .save_with_format(&pic_path, image::ImageFormat::Jpeg)
-        .map_err(io::Error::other)?;
+        .write_to(&mut Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+        .map_err(|e| AppError::Render(e.to_string()))?;
+    fs::write(pic_path, embed_manifest(&jpeg, manifest))?;
 
     // Too slow!
     // let mut output_file = fs::File::create(&pic_path)?;
     // let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut output_file, 80);
     // encoder.encode_image(&rgb_image).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
 
-    Ok(pic_path)
+    Ok(())
 }
 
-pub fn generate_single_card_pixmap(i: usize, info: &RatingInfo) -> io::Result<tiny_skia::Pixmap> {
-    let card_data = generate_card_cata(i, info);
-    let mut reg = Handlebars::new();
-    reg.register_template_file("template", INFO_CARD_TEMPLATE_PATH)
-        .expect("Failed to register template");
-    let svg_content: String = reg
-        .render("template", &json!(card_data))
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-
-    let options = usvg::Options {
-        fontdb: FONT_ARC.clone(),
-        ..Default::default()
+/// 导出实时模式的总结图片，包括统计数据和本次的最佳成绩卡片
+pub fn export_session_summary(summary: &SessionSummary) -> AppResult<PathBuf> {
+    let best_card = generate_single_card_pixmap(0, &summary.best_play)?
+        .encode_png()
+        .map_err(|e| AppError::Render(e.to_string()))?;
+    let format_time = |t: &chrono::DateTime<chrono::Utc>| {
+        t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
     };
+    let b30_delta = summary.b30_after - summary.b30_before;
+
+    let svg_content = RENDERER.render_template(
+        SESSION_TEMPLATE_PATH,
+        &json!({
+                "player_name": summary.player,
+                "time_range": format!(
+                    "{} ~ {}",
+                    format_time(&summary.started_at),
+                    format_time(&summary.ended_at)
+                ),
+                "generated_time": Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                "play_count": summary.play_count,
+                "time_played": format_length_str(summary.time_played),
+                "average_rating": format!("{:.02}", summary.average_rating),
+                "average_rating_fill": format_diff_gradient(summary.average_rating),
+                "new_b30_entries_label": tr!("进入B{}", summary.overall.slots),
+                "new_b30_entries": summary.new_b30_entries,
+                "b30_change_label": tr!("合并后的{}", summary.overall.short_label()),
+                "b30_before": format!("{:.02}", summary.b30_before),
+                "b30_before_fill": format_diff_gradient(summary.b30_before),
+                "b30_after": format!("{:.02}", summary.b30_after),
+                "b30_after_fill": format_diff_gradient(summary.b30_after),
+                "b30_delta": format!("{b30_delta:+.02}"),
+                "b30_delta_fill": if b30_delta > 0.0 { "#7cff4f" } else { "#cccccc" },
+                "best_card": BASE64_STANDARD.encode(&best_card),
+                "t": template_strings(),
+        }),
+    )?;
+    let pixmap = RENDERER.render_pixmap(&svg_content, SESSION_WIDTH, SESSION_HEIGHT)?;
+
+    let pic_name = format!(
+        "{}-session-{}.jpg",
+        summary.player,
+        summary
+            .ended_at
+            .with_timezone(&Local)
+            .format("%Y%m%d-%H%M%S")
+    );
+    let pic_path = export_dir()?.join(pic_name);
+    let manifest = ExportManifest::new(&summary.player, [(0, &summary.best_play)]);
+    save_jpeg(pixmap, &pic_path, &manifest)?;
+    Ok(pic_path)
+}
 
-    let tree = usvg::Tree::from_str(&svg_content, &options)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-
-    let mut pixmap = tiny_skia::Pixmap::new(CARD_WIDTH, CARD_HEIGHT)
-        .ok_or_else(|| io::Error::other("Failed to create pixmap"))?;
-
-    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
-
+/// 生成第i名的成绩卡片，同一成绩同一排名的卡片只渲染一次
+pub fn generate_single_card_pixmap(i: usize, info: &RatingInfo) -> AppResult<tiny_skia::Pixmap> {
+    let key = CardKey::new(i, info, INFO_CARD_TEMPLATE_PATH, CARD_SCALE);
+    if let Some(pixmap) = CARD_CACHE.get(&key) {
+        return Ok(pixmap);
+    }
+    let svg_content = generate_single_card_svg(i, info)?;
+    let pixmap = RENDERER.render_pixmap(&svg_content, CARD_WIDTH, CARD_HEIGHT)?;
+    CARD_CACHE.put(key, &pixmap);
     Ok(pixmap)
 }
 
-fn format_bpm_str(min_bpm: f64, max_bpm: Option<f64>) -> String {
+/// 生成第i名的成绩卡片的SVG，PDF导出时直接嵌入矢量的卡片
+pub(super) fn generate_single_card_svg(i: usize, info: &RatingInfo) -> AppResult<String> {
+    let background = load_card_background(info)?;
+    let card_data = generate_card_cata(i, info, &background);
+    RENDERER.render_template(INFO_CARD_TEMPLATE_PATH, &card_data)
+}
+
+pub fn format_bpm_str(min_bpm: f64, max_bpm: Option<f64>) -> String {
     let m_bpm = match max_bpm {
         Some(v) => v,
         None => min_bpm,
@@ -450,7 +633,7 @@ fn format_bpm_str(min_bpm: f64, max_bpm: Option<f64>) -> String {
     }
 }
 
-fn format_length_str(length: u32) -> String {
+pub fn format_length_str(length: u32) -> String {
     let mins = length / 60000;
     let secs = (length - 60000 * mins) / 1000;
     // let msecs = length % 1000;
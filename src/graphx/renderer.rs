@@ -0,0 +1,147 @@
+use base64::prelude::*;
+use handlebars::Handlebars;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use lru::LruCache;
+use resvg::{tiny_skia, usvg};
+use serde::Serialize;
+use std::io::Cursor;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+
+use crate::error::{AppError, AppResult};
+use crate::tr;
+
+const FONT_DIR_PATH: &str = "fonts";
+/// 最多缓存的背景图片数量，足够放下B30、备选区和实时模式的卡片
+const BACKGROUND_CACHE_SIZE: usize = 96;
+/// 左侧背景的大小
+const COVER_SIZE: (u32, u32) = (300, 300);
+/// 右侧模糊背景的大小，与模板中的image元素一致
+const BLURRED_SIZE: (u32, u32) = (920, 300);
+/// 与原来模板中feGaussianBlur的stdDeviation相同
+const BLUR_SIGMA: f32 = 8.0;
+
+/// 全局共享的渲染器
+pub static RENDERER: LazyLock<Renderer> = LazyLock::new(Renderer::new);
+
+/// 解码、缩放并模糊好的背景图片，以data URI的形式嵌入模板
+#[derive(Debug)]
+pub struct Background {
+    /// 左侧300x300的背景
+    pub cover: String,
+    /// 右侧920x300的模糊背景
+    pub blurred: String,
+}
+
+/// 长期持有的渲染器：编译好的模板、字体库以及背景图片的LRU缓存
+pub struct Renderer {
+    templates: RwLock<Handlebars<'static>>,
+    options: usvg::Options<'static>,
+    backgrounds: Mutex<LruCache<PathBuf, Arc<Background>>>,
+}
+
+impl Renderer {
+    fn new() -> Self {
+        let mut fontdb = usvg::fontdb::Database::new();
+        fontdb.load_fonts_dir(FONT_DIR_PATH);
+        fontdb.load_system_fonts();
+        let options = usvg::Options {
+            fontdb: Arc::new(fontdb),
+            ..Default::default()
+        };
+        Renderer {
+            templates: RwLock::new(Handlebars::new()),
+            options,
+            backgrounds: Mutex::new(LruCache::new(
+                NonZeroUsize::new(BACKGROUND_CACHE_SIZE).unwrap(),
+            )),
+        }
+    }
+
+    /// 用模板文件渲染SVG。模板在第一次使用时编译，读取失败时下次还会重试
+    pub fn render_template(
+        &self,
+        template_path: &'static str,
+        data: &impl Serialize,
+    ) -> AppResult<String> {
+        {
+            let templates = self.templates.read().unwrap();
+            if templates.has_template(template_path) {
+                return templates
+                    .render(template_path, data)
+                    .map_err(|e| AppError::Render(e.to_string()));
+            }
+        }
+        let mut templates = self.templates.write().unwrap();
+        if !templates.has_template(template_path) {
+            templates
+                .register_template_file(template_path, template_path)
+                .map_err(|e| AppError::Template {
+                    path: template_path,
+                    reason: e.to_string(),
+                })?;
+        }
+        templates
+            .render(template_path, data)
+            .map_err(|e| AppError::Render(e.to_string()))
+    }
+
+    /// 解析SVG，使用共享的字体库
+    pub fn parse_tree(&self, svg_content: &str) -> AppResult<usvg::Tree> {
+        usvg::Tree::from_str(svg_content, &self.options)
+            .map_err(|e| AppError::Render(e.to_string()))
+    }
+
+    /// 把SVG渲染到指定大小的画布上
+    pub fn render_pixmap(
+        &self,
+        svg_content: &str,
+        width: u32,
+        height: u32,
+    ) -> AppResult<tiny_skia::Pixmap> {
+        let tree = self.parse_tree(svg_content)?;
+        let mut pixmap = tiny_skia::Pixmap::new(width, height)
+            .ok_or_else(|| AppError::Render(tr!("无法创建画布")))?;
+        resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+        Ok(pixmap)
+    }
+
+    /// 读取背景图片，已经处理过的直接从缓存中取出
+    pub fn background(&self, path: &Path) -> AppResult<Arc<Background>> {
+        if let Some(background) = self.backgrounds.lock().unwrap().get(path) {
+            return Ok(background.clone());
+        }
+        // 解码和模糊比较慢，不持有锁，多个线程同时处理同一张图片也只是重复计算
+        let background = Arc::new(load_background(path)?);
+        self.backgrounds
+            .lock()
+            .unwrap()
+            .put(path.to_path_buf(), background.clone());
+        Ok(background)
+    }
+}
+
+fn load_background(path: &Path) -> AppResult<Background> {
+    let image = image::open(path)
+        .map_err(|e| AppError::Render(tr!("无法读取图片 {}: {}", path.display(), e)))?;
+    let cover = image.resize_to_fill(COVER_SIZE.0, COVER_SIZE.1, FilterType::Triangle);
+    let wide = image.resize_to_fill(BLURRED_SIZE.0, BLURRED_SIZE.1, FilterType::Triangle);
+    let blurred = DynamicImage::ImageRgba8(image::imageops::blur(&wide, BLUR_SIGMA));
+    Ok(Background {
+        cover: to_data_uri(&cover)?,
+        blurred: to_data_uri(&blurred)?,
+    })
+}
+
+fn to_data_uri(image: &DynamicImage) -> AppResult<String> {
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .map_err(|e| AppError::Render(e.to_string()))?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        BASE64_STANDARD.encode(bytes)
+    ))
+}
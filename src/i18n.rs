@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 程序支持的语言。中文是源语言，其他语言的翻译位于lang/<code>/LC_MESSAGES/mania-rating-gui.po，
+/// 界面中的@tr和Rust中的tr!共用这些文件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    Zh,
+    En,
+    Ja,
+}
+
+impl Language {
+    pub const ALL: [Language; 3] = [Language::Zh, Language::En, Language::Ja];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::Zh => "zh",
+            Language::En => "en",
+            Language::Ja => "ja",
+        }
+    }
+
+    /// 设置中显示的名称，总是使用该语言本身书写
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Language::Zh => "中文",
+            Language::En => "English",
+            Language::Ja => "日本語",
+        }
+    }
+
+    fn catalog(self) -> Option<&'static HashMap<String, String>> {
+        match self {
+            Language::Zh => None,
+            Language::En => Some(&EN_CATALOG),
+            Language::Ja => Some(&JA_CATALOG),
+        }
+    }
+}
+
+static CURRENT_LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+static EN_CATALOG: LazyLock<HashMap<String, String>> =
+    LazyLock::new(|| parse_po(include_str!("../lang/en/LC_MESSAGES/mania-rating-gui.po")));
+static JA_CATALOG: LazyLock<HashMap<String, String>> =
+    LazyLock::new(|| parse_po(include_str!("../lang/ja/LC_MESSAGES/mania-rating-gui.po")));
+
+pub fn current_language() -> Language {
+    Language::ALL[CURRENT_LANGUAGE.load(Ordering::Relaxed) as usize]
+}
+
+/// 切换语言，界面中的文本会立即更新
+pub fn set_language(language: Language) {
+    let index = Language::ALL
+        .iter()
+        .position(|l| *l == language)
+        .unwrap_or(0);
+    CURRENT_LANGUAGE.store(index as u8, Ordering::Relaxed);
+    if let Err(e) = slint::select_bundled_translation(language.code()) {
+        log::warn!("Failed to select translation {}: {e}", language.code());
+    }
+}
+
+/// 当前语言下msgid的翻译，没有翻译时返回原文
+pub fn tr(msgid: &str) -> String {
+    current_language()
+        .catalog()
+        .and_then(|catalog| catalog.get(msgid))
+        .cloned()
+        .unwrap_or_else(|| msgid.to_string())
+}
+
+/// 翻译后依次替换其中的{}，或按序号替换{0}、{1}，与Slint中@tr的格式相同
+pub fn tr_format(msgid: &str, args: &[String]) -> String {
+    let pattern = tr(msgid);
+    let mut result = String::with_capacity(pattern.len());
+    let mut next_arg = 0;
+    let mut rest = pattern.as_str();
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let inner = &rest[start + 1..start + len];
+        let index = if inner.is_empty() {
+            next_arg += 1;
+            Some(next_arg - 1)
+        } else {
+            inner.parse::<usize>().ok()
+        };
+        match index.and_then(|i| args.get(i)) {
+            Some(arg) => result.push_str(arg),
+            None => result.push_str(&rest[start..=start + len]),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// 翻译文本，`tr!("已载入{}个成绩", count)`
+#[macro_export]
+macro_rules! tr {
+    ($msgid:literal) => {
+        $crate::i18n::tr($msgid)
+    };
+    ($msgid:literal, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::tr_format($msgid, &[$($arg.to_string()),+])
+    };
+}
+
+/// 模板中引用的文本，在模板中写作{{t.key}}
+const TEMPLATE_STRINGS: &[(&str, &str)] = &[
+    ("app_title", "6K Rating 计算器"),
+    (
+        "version_line",
+        "版本 0.2    Made by Siflorite    2025.07.25",
+    ),
+    ("generated_time", "生成时间: "),
+    ("algorithm_version", "算法版本: "),
+    ("disclaimer_title", "免责声明："),
+    (
+        "disclaimer_1",
+        "1. 本程序对osu!stable安装目录下的 osu!.db 和 scores.db 进行分析，通过scores.db中游玩记录的谱面MD5找到Songs文件夹中对应的.osu谱面文件，筛选出模式为Mania 6K且有游玩记录的谱面。使用sunnyxxy的Star-Rebirth 20250415版本计算星级，使用sunnyxxy的Rating算法计算玩家表现。目前无法用于osu!lazer。",
+    ),
+    (
+        "disclaimer_2",
+        "2. 由于osu!中的数据库使用明文储存，本程序没有任何反作弊手段，无法读取和验证玩家的Replay，仅读取存在的分数。且星级算法和Rating算法仍在早期开发阶段，对LN和高速等谱面SR测定仍然较高。此外可能计入重复的谱面，本程序所测数据仅供参考。",
+    ),
+    (
+        "disclaimer_3",
+        "3. 本程序没有任何联网功能，不会读取玩家的个人隐私数据，也不会向互联网上传和下载任何内容。本程序只读取本地数据库，不会修改任何数据库内容。由于osu!.db内部格式经常修改，本程序适配版本为20250401版本的数据库，后续可能因版本变化导致无法运行。",
+    ),
+    ("session_title", "实时模式总结"),
+    ("play_count", "游玩次数"),
+    ("time_played", "游玩时长"),
+    ("session_average_rating", "平均Rating"),
+    ("best_play", "最佳成绩"),
+    ("appendix_title", "成绩附表"),
+    ("col_map", "谱面"),
+    ("col_mods", "Mods"),
+    ("col_judgements", "判定"),
+    ("col_acc", "Acc"),
+    ("col_acc_rating", "Rating Acc"),
+    ("col_sr", "星级"),
+    ("col_diff_const", "定数"),
+    ("col_rating", "Rating"),
+    ("col_time", "时间"),
+    ("overlay_waiting", "等待连接..."),
+    ("overlay_reconnecting", "连接断开，正在重连..."),
+    ("overlay_started", "游玩中"),
+    ("overlay_restarted", "重新开始"),
+    ("overlay_failed", "游玩失败"),
+    ("overlay_quit", "退出游玩"),
+    ("overlay_replay", "观看回放"),
+    ("overlay_diff_const", "定数"),
+    ("overlay_entered", "进入B{} #{}"),
+    ("overlay_replaced", ", 替换 {} ({})"),
+    ("overlay_not_entered", "未进入B{}"),
+];
+
+/// 传入Handlebars模板的文本表
+pub fn template_strings() -> HashMap<&'static str, String> {
+    TEMPLATE_STRINGS
+        .iter()
+        .map(|(key, msgid)| (*key, tr(msgid)))
+        .collect()
+}
+
+/// 读取.po文件中没有msgctxt的条目。带msgctxt的条目属于Slint界面，由Slint自己处理
+fn parse_po(content: &str) -> HashMap<String, String> {
+    #[derive(PartialEq)]
+    enum Field {
+        None,
+        Context,
+        Id,
+        Str,
+    }
+    let mut catalog = HashMap::new();
+    let (mut context, mut msgid, mut msgstr) = (None::<String>, String::new(), String::new());
+    let mut field = Field::None;
+    let mut flush = |context: &mut Option<String>, msgid: &mut String, msgstr: &mut String| {
+        if context.is_none() && !msgid.is_empty() && !msgstr.is_empty() {
+            catalog.insert(std::mem::take(msgid), std::mem::take(msgstr));
+        }
+        *context = None;
+        msgid.clear();
+        msgstr.clear();
+    };
+
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (next_field, quoted) = if let Some(rest) = line.strip_prefix("msgctxt ") {
+            (Field::Context, rest)
+        } else if let Some(rest) = line.strip_prefix("msgid ") {
+            (Field::Id, rest)
+        } else if let Some(rest) = line.strip_prefix("msgstr ") {
+            (Field::Str, rest)
+        } else {
+            (Field::None, line)
+        };
+        // 新条目从msgctxt或msgid开始
+        if (next_field == Field::Context || (next_field == Field::Id && field != Field::Context))
+            && field != Field::None
+        {
+            flush(&mut context, &mut msgid, &mut msgstr);
+        }
+        if next_field != Field::None {
+            field = next_field;
+        }
+        let quoted = quoted.strip_prefix('"').unwrap_or(quoted);
+        let text = unescape(quoted.strip_suffix('"').unwrap_or(quoted));
+        match field {
+            Field::Context => context.get_or_insert_with(String::new).push_str(&text),
+            Field::Id => msgid.push_str(&text),
+            Field::Str => msgstr.push_str(&text),
+            Field::None => {}
+        }
+    }
+    flush(&mut context, &mut msgid, &mut msgstr);
+    catalog
+}
+
+fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(other) => result.push(other),
+            None => {}
+        }
+    }
+    result
+}
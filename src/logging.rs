@@ -0,0 +1,78 @@
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const LOG_DIR: &str = "logs";
+const LOG_FILE: &str = "mania-rating-gui.log";
+/// 日志等级的环境变量，如 MANIA_RATING_LOG=debug
+const LOG_LEVEL_ENV: &str = "MANIA_RATING_LOG";
+
+/// 写入程序目录下logs文件夹的日志，上一次运行的日志保留为.old.log。
+/// 其他库的日志只记录警告以上
+struct FileLogger {
+    file: Mutex<File>,
+    level: LevelFilter,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        if metadata.target().starts_with(env!("CARGO_CRATE_NAME")) {
+            metadata.level() <= self.level
+        } else {
+            metadata.level() <= LevelFilter::Warn
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} [{:<5}] {}: {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        // 发布版本没有控制台
+        if cfg!(debug_assertions) {
+            eprint!("{line}");
+        }
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// 初始化日志，返回日志文件的路径
+pub fn init_logging() -> io::Result<PathBuf> {
+    let log_dir = env::current_dir()?.join(LOG_DIR);
+    fs::create_dir_all(&log_dir)?;
+    let path = log_dir.join(LOG_FILE);
+    if path.exists() {
+        let _ = fs::rename(&path, path.with_extension("old.log"));
+    }
+    let file = File::create(&path)?;
+
+    let level = env::var(LOG_LEVEL_ENV)
+        .ok()
+        .and_then(|level| level.parse().ok())
+        .unwrap_or(LevelFilter::Info);
+    log::set_boxed_logger(Box::new(FileLogger {
+        file: Mutex::new(file),
+        level,
+    }))
+    .map_err(io::Error::other)?;
+    log::set_max_level(level.max(LevelFilter::Warn));
+    Ok(path)
+}
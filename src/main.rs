@@ -1,26 +1,71 @@
 #![windows_subsystem = "windows"]
 pub mod db;
+pub mod error;
 pub mod graphx;
+pub mod i18n;
+pub mod logging;
+pub mod overlay;
+pub mod settings;
 pub mod ui;
 
-use crate::db::{RatingInfo, get_osu_install_path, prepare_ratings};
-use crate::ui::bs::update_realtime;
+use crate::db::{
+    DedupePolicy, INGESTION_REPORT, IngestionProgress, MAX_SLOTS, OverallRating, RatingInfo,
+    get_osu_install_path, imported_ratings, merge_ratings, prepare_ratings, table_size,
+};
+use crate::graphx::ExportFormat;
+use crate::i18n::{Language, current_language, set_language};
+use crate::logging::init_logging;
+use crate::overlay::{OVERLAY_PORT, serve_overlay};
+use crate::settings::{SETTINGS, Settings};
+use crate::ui::bs::update_realtime_async;
 use crate::ui::callbacks::{
-    add_tile, copy_image, export, remove_tile, select_osu_folder, update_player_b30,
+    accept_pending_score, add_tile, collect_player_names, copy_image, export, export_session,
+    load_session, move_tile, refresh_overall_rating, remove_tile, select_osu_folder,
+    select_session_file, select_status_mirror_file, toggle_pin, update_player_b30,
 };
-use crate::ui::{ScoreTileBase64, ThreadManager};
+use crate::ui::details::{player_card, realtime_card, show_score_details};
+use crate::ui::history::show_history_export;
+use crate::ui::notify::{dismiss_notification, init_notifications, notify};
+use crate::ui::picker::show_score_picker;
+use crate::ui::verify::verify_export;
+use crate::ui::watcher::{stop_watching, watch_osu_dir};
+use crate::ui::{ScoreTileBase64, ThreadManagerAsync};
 use anyhow::Result;
-use slint::{ModelRc, SharedString, VecModel, Weak};
+use log::Level;
+use slint::{ModelRc, SharedString, StandardListViewItem, VecModel, Weak};
 use std::collections::HashMap;
 use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
 slint::include_modules!();
 type LazyScoreMap = LazyLock<Arc<Mutex<HashMap<String, Vec<RatingInfo>>>>>;
 static SCORES_DATA: LazyScoreMap = LazyLock::new(|| Arc::new(Mutex::new(HashMap::new())));
+type LazyRealtimeList = LazyLock<Arc<Mutex<Vec<RatingInfo>>>>;
+// 同时需要SCORES_DATA时先锁REALTIME_DATA
+static REALTIME_DATA: LazyRealtimeList = LazyLock::new(|| Arc::new(Mutex::new(Vec::new())));
+// 实时模式中进入B30、等待加入成绩表的最新成绩
+static PENDING_SCORE: LazyLock<Mutex<Option<RatingInfo>>> = LazyLock::new(|| Mutex::new(None));
+// 正在进行的读取，选择其他文件夹时会被取消
+static CURRENT_INGESTION: LazyLock<Mutex<Option<Arc<IngestionProgress>>>> =
+    LazyLock::new(|| Mutex::new(None));
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    if let Err(e) = init_logging() {
+        eprintln!("Failed to initialize logging: {e}");
+    }
     let ui = MainWindow::new()?;
+    set_language(SETTINGS.lock().unwrap().language);
+    init_notifications(ui.as_weak());
+    let ui_dismiss = ui.as_weak();
+    ui.on_dismiss_notification(move |id| {
+        if let Some(ui) = ui_dismiss.upgrade() {
+            dismiss_notification(&ui, id);
+        }
+    });
     let osu_path = get_osu_install_path();
     // let osu_path: Option<std::path::PathBuf> = None; // For testing
     let osu_exe_dir = match osu_path {
@@ -28,7 +73,6 @@ async fn main() -> Result<()> {
         None => select_osu_folder().ok_or(anyhow::Error::msg("Cannot find osu directory"))?,
     };
     ui.set_osu_dir(SharedString::from(&osu_exe_dir));
-    initialize(osu_exe_dir.clone(), ui.as_weak()).await?;
 
     let ratings = SCORES_DATA.clone();
 
@@ -37,9 +81,9 @@ async fn main() -> Result<()> {
     ui.on_selection_changed(move |player_name| {
         let rating_selection = rating_selection.clone();
         let ui_selection = ui_handle.clone();
-        ui_selection
-            .unwrap()
-            .set_text_content(SharedString::from("正在加载..."));
+        if let Some(ui) = ui_selection.upgrade() {
+            ui.set_text_content(SharedString::from(tr!("正在加载...")));
+        }
         tokio::spawn(update_player_b30(
             player_name,
             rating_selection,
@@ -50,136 +94,526 @@ async fn main() -> Result<()> {
     let ui_reset = ui.as_weak();
     let rating_reset = ratings.clone();
     ui.on_reset_tiles(move || {
-        let ui = ui_reset.clone();
-        let player_name = ui_reset.unwrap().get_current_player_name();
+        let Some(ui) = ui_reset.upgrade() else {
+            return;
+        };
+        let player_name = ui.get_current_player_name();
         let rating = rating_reset.clone();
-        ui.unwrap()
-            .set_text_content(SharedString::from("正在加载..."));
-        tokio::spawn(update_player_b30(player_name, rating, ui));
+        ui.set_text_content(SharedString::from(tr!("正在加载...")));
+        tokio::spawn(update_player_b30(player_name, rating, ui_reset.clone()));
     });
 
     let ui_remove = ui.as_weak();
     let rating_remove = ratings.clone();
     ui.on_removed(move |index| {
+        let Some(ui) = ui_remove.upgrade() else {
+            return;
+        };
         let ui_handle = ui_remove.clone();
-        let player_name = ui_remove.unwrap().get_current_player_name();
+        let player_name = ui.get_current_player_name();
         let rating_remove = rating_remove.clone();
-        ui_handle
-            .unwrap()
-            .set_text_content(SharedString::from("正在加载..."));
+        ui.set_text_content(SharedString::from(tr!("正在加载...")));
         tokio::spawn(remove_tile(player_name, index, rating_remove, ui_handle));
     });
 
     let ui_add = ui.as_weak();
     let rating_add = ratings.clone();
     ui.on_added(move |index| {
+        let Some(ui) = ui_add.upgrade() else {
+            return;
+        };
         let ui_handle = ui_add.clone();
-        let player_name = ui_add.unwrap().get_current_player_name();
+        let player_name = ui.get_current_player_name();
         let rating_add = rating_add.clone();
-        ui_handle
-            .unwrap()
-            .set_text_content(SharedString::from("正在加载..."));
+        ui.set_text_content(SharedString::from(tr!("正在加载...")));
         tokio::spawn(add_tile(player_name, index, rating_add, ui_handle));
     });
 
+    let ui_pin = ui.as_weak();
+    ui.on_pin_toggled(move |index| toggle_pin(index, ui_pin.clone()));
+
+    let ui_move = ui.as_weak();
+    ui.on_moved(move |index, slot| move_tile(index, slot, ui_move.clone()));
+
+    let ui_history = ui.as_weak();
+    ui.on_show_history_export(move || {
+        if let Some(ui) = ui_history.upgrade() {
+            show_history_export(&ui);
+        }
+    });
+
+    ui.on_verify_export(verify_export);
+
+    let ui_picker = ui.as_weak();
+    ui.on_show_score_picker(move || {
+        if let Some(ui) = ui_picker.upgrade() {
+            show_score_picker(&ui);
+        }
+    });
+
     let ui_export = ui.as_weak();
     let rating_export = ratings.clone();
     ui.on_export(move || {
+        let Some(ui) = ui_export.upgrade() else {
+            return;
+        };
         let ui_handle = ui_export.clone();
-        let player_name = ui_export.unwrap().get_current_player_name();
+        let player_name = ui.get_current_player_name();
         let rating_export = rating_export.clone();
-        ui_handle
-            .unwrap()
-            .set_text_content(SharedString::from("正在导出..."));
+        ui.set_text_content(SharedString::from(tr!("正在导出...")));
         tokio::spawn(export(player_name, rating_export, ui_handle));
     });
 
     ui.on_show_help_window(move || {
-        let help_window = HelpWindow::new().unwrap();
-        help_window.show().unwrap();
+        let help_window = match HelpWindow::new() {
+            Ok(help_window) => help_window,
+            Err(e) => {
+                notify(Level::Error, tr!("无法打开使用说明: {}", e));
+                return;
+            }
+        };
+        if let Err(e) = help_window.show() {
+            notify(Level::Error, tr!("无法打开使用说明: {}", e));
+            return;
+        }
         help_window.on_open_help_url(move || {
-            open::that("https://github.com/Siflorite/mania-rating-gui").unwrap();
+            if let Err(e) = open::that("https://github.com/Siflorite/mania-rating-gui") {
+                notify(Level::Warn, tr!("无法打开浏览器: {}", e));
+            }
+        });
+    });
+
+    ui.on_show_report(move || {
+        let report = INGESTION_REPORT.lock().unwrap().clone();
+        let report_window = match ReportWindow::new() {
+            Ok(report_window) => report_window,
+            Err(e) => {
+                notify(Level::Error, tr!("无法打开读取报告: {}", e));
+                return;
+            }
+        };
+        let reasons = report
+            .reason_counts()
+            .into_iter()
+            .map(|(reason, count)| format!("{count} × {reason}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let items = report
+            .item_lines()
+            .into_iter()
+            .map(|line| StandardListViewItem::from(SharedString::from(line)))
+            .collect::<Vec<_>>();
+        report_window.set_summary(SharedString::from(report.summary()));
+        report_window.set_reasons(SharedString::from(reasons));
+        report_window.set_items(ModelRc::new(VecModel::from(items)));
+        report_window.on_export_report(move || match report.export() {
+            Ok(path) => {
+                notify(Level::Info, tr!("读取报告已导出到 {}", path.display()));
+                if let Err(e) = open::that(&path) {
+                    log::warn!("Failed to open {}: {e}", path.display());
+                }
+            }
+            Err(e) => notify(Level::Error, tr!("导出读取报告失败: {}", e)),
+        });
+        if let Err(e) = report_window.show() {
+            notify(Level::Error, tr!("无法打开读取报告: {}", e));
+        }
+    });
+
+    let ui_settings = ui.as_weak();
+    ui.on_show_settings(move || {
+        let settings_window = match SettingsWindow::new() {
+            Ok(settings_window) => settings_window,
+            Err(e) => {
+                notify(Level::Error, tr!("无法打开设置: {}", e));
+                return;
+            }
+        };
+        let languages = Language::ALL
+            .iter()
+            .map(|language| SharedString::from(language.display_name()))
+            .collect::<Vec<_>>();
+        let current = Language::ALL
+            .iter()
+            .position(|language| *language == current_language())
+            .unwrap_or(0);
+        settings_window.set_languages(ModelRc::new(VecModel::from(languages)));
+        settings_window.set_language_index(current as i32);
+        {
+            let settings = SETTINGS.lock().unwrap();
+            settings_window.set_ranked_only(settings.b30_ranked_only);
+            let dedupe_options = DedupePolicy::ALL
+                .iter()
+                .map(|policy| SharedString::from(policy.label()))
+                .collect::<Vec<_>>();
+            let dedupe_index = DedupePolicy::ALL
+                .iter()
+                .position(|policy| *policy == settings.dedupe)
+                .unwrap_or(0);
+            settings_window.set_dedupe_options(ModelRc::new(VecModel::from(dedupe_options)));
+            settings_window.set_dedupe_index(dedupe_index as i32);
+            let overall_options = OverallRating::ALL
+                .iter()
+                .map(|mode| SharedString::from(mode.label()))
+                .collect::<Vec<_>>();
+            let overall_index = OverallRating::ALL
+                .iter()
+                .position(|mode| *mode == settings.overall)
+                .unwrap_or(0);
+            settings_window.set_overall_options(ModelRc::new(VecModel::from(overall_options)));
+            settings_window.set_overall_index(overall_index as i32);
+            settings_window.set_overall_slots(settings.overall_slots as i32);
+            settings_window.set_max_slots(MAX_SLOTS as i32);
+            let format_options = ExportFormat::ALL
+                .iter()
+                .map(|format| SharedString::from(format.label()))
+                .collect::<Vec<_>>();
+            let format_index = ExportFormat::ALL
+                .iter()
+                .position(|format| *format == settings.export_format)
+                .unwrap_or(0);
+            settings_window.set_format_options(ModelRc::new(VecModel::from(format_options)));
+            settings_window.set_format_index(format_index as i32);
+            settings_window.set_status_mirror(SharedString::from(
+                settings
+                    .status_mirror
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            ));
+        }
+        let ui_language = ui_settings.clone();
+        settings_window.on_language_changed(move |index| {
+            let Some(language) = Language::ALL.get(index as usize).copied() else {
+                return;
+            };
+            set_language(language);
+            update_settings(|settings| settings.language = language);
+            if let Some(ui) = ui_language.upgrade() {
+                refresh_overall_rating(&ui);
+            }
+        });
+        let ui_ranked = ui_settings.clone();
+        settings_window.on_ranked_only_changed(move |ranked_only| {
+            update_settings(|settings| settings.b30_ranked_only = ranked_only);
+            reload_osu_dir(&ui_ranked);
+        });
+        let ui_dedupe = ui_settings.clone();
+        settings_window.on_dedupe_changed(move |index| {
+            let Some(policy) = DedupePolicy::ALL.get(index as usize).copied() else {
+                return;
+            };
+            update_settings(|settings| settings.dedupe = policy);
+            reload_osu_dir(&ui_dedupe);
+        });
+        let ui_overall = ui_settings.clone();
+        settings_window.on_overall_changed(move |index| {
+            let Some(mode) = OverallRating::ALL.get(index as usize).copied() else {
+                return;
+            };
+            update_settings(|settings| settings.overall = mode);
+            if let Some(ui) = ui_overall.upgrade() {
+                refresh_overall_rating(&ui);
+            }
+        });
+        let ui_slots = ui_settings.clone();
+        settings_window.on_overall_slots_changed(move |slots| {
+            let slots = (slots.max(1) as usize).min(MAX_SLOTS);
+            let old_size = table_size();
+            update_settings(|settings| settings.overall_slots = slots);
+            if let Some(ui) = ui_slots.upgrade() {
+                // 成绩表的卡片数量变化时重新生成成绩表
+                if table_size() != old_size {
+                    ui.invoke_reset_tiles();
+                } else {
+                    refresh_overall_rating(&ui);
+                }
+            }
+        });
+        settings_window.on_format_changed(move |index| {
+            let Some(format) = ExportFormat::ALL.get(index as usize).copied() else {
+                return;
+            };
+            update_settings(|settings| settings.export_format = format);
+        });
+        let ui_mirror = ui_settings.clone();
+        let window_mirror = settings_window.as_weak();
+        settings_window.on_select_status_mirror(move || {
+            let Some(path) = select_status_mirror_file() else {
+                return;
+            };
+            if let Some(window) = window_mirror.upgrade() {
+                window.set_status_mirror(SharedString::from(path.to_string_lossy().as_ref()));
+            }
+            update_settings(|settings| settings.status_mirror = Some(path));
+            reload_osu_dir(&ui_mirror);
         });
+        let ui_clear_mirror = ui_settings.clone();
+        let window_clear_mirror = settings_window.as_weak();
+        settings_window.on_clear_status_mirror(move || {
+            if let Some(window) = window_clear_mirror.upgrade() {
+                window.set_status_mirror(SharedString::new());
+            }
+            update_settings(|settings| settings.status_mirror = None);
+            reload_osu_dir(&ui_clear_mirror);
+        });
+        if let Err(e) = settings_window.show() {
+            notify(Level::Error, tr!("无法打开设置: {}", e));
+        }
+    });
+
+    let ui_details = ui.as_weak();
+    ui.on_show_details(move |index| {
+        let Some(ui) = ui_details.upgrade() else {
+            return;
+        };
+        match player_card(ui.get_current_player_name().as_str(), index as usize) {
+            Some(info) => show_score_details(info, ui.get_osu_dir().to_string()),
+            None => log::warn!("No score at index {index}"),
+        }
+    });
+
+    let ui_realtime_details = ui.as_weak();
+    ui.on_show_realtime_details(move |index| {
+        let Some(ui) = ui_realtime_details.upgrade() else {
+            return;
+        };
+        match realtime_card(index as usize) {
+            Some(info) => show_score_details(info, ui.get_osu_dir().to_string()),
+            None => log::warn!("No realtime score at index {index}"),
+        }
     });
 
     ui.on_copied(move |image| {
-        let raw_data = image.to_rgba8().unwrap();
+        let Some(raw_data) = image.to_rgba8() else {
+            notify(Level::Warn, tr!("无法读取卡片图像"));
+            return;
+        };
         let (width, height, bytes) = (
             raw_data.width() as usize,
             raw_data.height() as usize,
             raw_data.as_bytes().to_vec(),
         );
-        tokio::spawn(copy_image(width, height, bytes));
+        tokio::spawn(async move {
+            if let Err(e) = copy_image(width, height, bytes).await {
+                notify(Level::Error, tr!("复制失败: {}", e));
+            }
+        });
     });
 
     let ui_update = ui.as_weak();
-    let mut thread_manager = ThreadManager::new();
+    let (status_sender, mut status_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let manager_lock = Arc::new(tokio::sync::Mutex::new(ThreadManagerAsync::new(
+        status_sender,
+    )));
     ui.on_toggle_realtime(move |status| {
         let ui_update = ui_update.clone();
-        if status {
-            thread_manager.start_thread(update_realtime, ui_update);
-        } else {
-            thread_manager.stop_thread();
-        }
-    });
-
-    // let thread_manager = ThreadManagerAsync::new();
-    // let manager_lock = Arc::new(Mutex::new(thread_manager));
-    // ui.on_toggle_realtime(move |status| {
-    //     let ui_update = ui_update.clone();
-    //     let manager_lock = manager_lock.clone();
-    //     tokio::spawn(async move {
-    //         if status {
-    //             manager_lock.lock().await.start_thread(update_realtime_async, ui_update).await;
-    //         } else {
-    //             manager_lock.lock().await.stop_thread().await;
-    //         }
-    //     });
-    // });
+        let manager_lock = manager_lock.clone();
+        tokio::spawn(async move {
+            let mut manager = manager_lock.lock().await;
+            if status {
+                manager.start_thread(update_realtime_async, ui_update).await;
+            } else {
+                manager.stop_thread().await;
+            }
+        });
+    });
+
+    let ui_status = ui.as_weak();
+    tokio::spawn(async move {
+        while let Some(status) = status_receiver.recv().await {
+            let _ = ui_status.upgrade_in_event_loop(move |ui| {
+                ui.set_test_content(SharedString::from(status.message()));
+            });
+        }
+    });
+
+    ui.set_overlay_url(slint::format!("http://127.0.0.1:{}", OVERLAY_PORT));
+    let ui_overlay = ui.as_weak();
+    let mut overlay_task: Option<(CancellationToken, JoinHandle<()>)> = None;
+    ui.on_toggle_overlay(move |status| {
+        let previous = overlay_task.take().map(|(cancel, task)| {
+            cancel.cancel();
+            task
+        });
+        if !status {
+            return;
+        }
+        let cancel = CancellationToken::new();
+        let ui_overlay = ui_overlay.clone();
+        let task_cancel = cancel.clone();
+        let task = tokio::spawn(async move {
+            // 等待上一个服务器释放端口后再重新监听
+            if let Some(previous) = previous {
+                let _ = previous.await;
+            }
+            if let Err(e) = serve_overlay(OVERLAY_PORT, task_cancel).await {
+                notify(Level::Error, tr!("叠加层启动失败: {}", e));
+                let _ = ui_overlay.upgrade_in_event_loop(|ui| {
+                    ui.set_overlay_enabled(false);
+                });
+            }
+        });
+        overlay_task = Some((cancel, task));
+    });
 
     let ui_refresh = ui.as_weak();
     ui.on_refresh(move || {
-        ui_refresh.unwrap().window().request_redraw();
+        if let Some(ui) = ui_refresh.upgrade() {
+            ui.window().request_redraw();
+        }
+    });
+
+    let ui_clear = ui.as_weak();
+    ui.on_clear_realtime(move || {
+        REALTIME_DATA.lock().unwrap().clear();
+        let Some(ui) = ui_clear.upgrade() else {
+            return;
+        };
+        ui.set_realtime_tiles(ModelRc::new(VecModel::from(Vec::new())));
+        ui.set_session_summary(SharedString::new());
+        ui.set_b30_delta(SharedString::new());
+        ui.set_live_estimate(SharedString::new());
+        ui.set_pending_score(false);
+        *PENDING_SCORE.lock().unwrap() = None;
+    });
+
+    let ui_accept = ui.as_weak();
+    ui.on_accept_pending(move || {
+        accept_pending_score(ui_accept.clone());
+    });
+
+    let ui_export_session = ui.as_weak();
+    ui.on_export_session(move || {
+        tokio::spawn(export_session(ui_export_session.clone()));
+    });
+
+    let ui_session = ui.as_weak();
+    ui.on_load_session(move || {
+        if let Some(path) = select_session_file() {
+            tokio::spawn(load_session(path, ui_session.clone()));
+        }
     });
 
     let ui_s = ui.as_weak();
     ui.on_select_osu_dir(move || {
+        let Some(ui) = ui_s.upgrade() else {
+            return;
+        };
         let new_dir = select_osu_folder().unwrap_or_default();
-        if !new_dir.is_empty() && new_dir != osu_exe_dir {
-            ui_s.unwrap().set_osu_dir(SharedString::from(&new_dir));
-            let ui_s = ui_s.clone();
-            tokio::spawn(initialize(new_dir, ui_s));
-        } else {
-            ui_s.unwrap().set_folder_select_enable(true);
+        if !new_dir.is_empty() && new_dir != ui.get_osu_dir().as_str() {
+            ui.set_osu_dir(SharedString::from(&new_dir));
+            start_initialize(new_dir, ui_s.clone());
         }
+        // 读取过程中也可以选择其他文件夹，之前的读取会被取消
+        ui.set_folder_select_enable(true);
     });
 
+    start_initialize(osu_exe_dir, ui.as_weak());
     ui.run()?;
     Ok(())
 }
 
-pub async fn initialize(osu_exe_dir: String, ui: Weak<MainWindow>) -> Result<()> {
-    let data = prepare_ratings(&osu_exe_dir)?;
-    let mut players_list = {
+/// 修改并保存设置，保存失败时通知用户
+fn update_settings(f: impl FnOnce(&mut Settings)) {
+    let mut settings = SETTINGS.lock().unwrap();
+    f(&mut settings);
+    if let Err(e) = settings.save() {
+        log::error!("Failed to save settings: {e}");
+        notify(Level::Error, tr!("保存设置失败: {}", e));
+    }
+}
+
+/// 影响成绩表的设置修改后，重新读取当前的osu!文件夹
+fn reload_osu_dir(ui: &Weak<MainWindow>) {
+    if let Some(ui) = ui.upgrade() {
+        start_initialize(ui.get_osu_dir().to_string(), ui.as_weak());
+    }
+}
+
+/// 在后台读取osu!文件夹并显示进度，取消之前还没有完成的读取
+fn start_initialize(osu_exe_dir: String, ui: Weak<MainWindow>) {
+    let progress = Arc::new(IngestionProgress::new());
+    if let Some(previous) = CURRENT_INGESTION.lock().unwrap().replace(progress.clone()) {
+        previous.cancel();
+    }
+    // 旧文件夹的后台刷新不能覆盖新读取的成绩
+    stop_watching();
+    tokio::spawn(async move {
+        let reporter = tokio::spawn(report_progress(progress.clone(), ui.clone()));
+        let result = initialize(osu_exe_dir.clone(), progress.clone(), ui.clone()).await;
+        reporter.abort();
+        if progress.is_cancelled() {
+            // 进度条由新的读取接管
+            log::info!("Cancelled reading osu! directory {osu_exe_dir}");
+            return;
+        }
+        let _ = ui.upgrade_in_event_loop(|ui| ui.set_loading(false));
+        if let Err(e) = result {
+            log::error!("Failed to read osu! directory {osu_exe_dir}: {e}");
+            notify(Level::Error, tr!("读取osu!文件夹失败: {}", e));
+        }
+    });
+}
+
+async fn report_progress(progress: Arc<IngestionProgress>, ui: Weak<MainWindow>) {
+    loop {
+        let snapshot = progress.snapshot();
+        let _ = ui.upgrade_in_event_loop(move |ui| {
+            ui.set_loading(true);
+            ui.set_load_indeterminate(snapshot.fraction().is_none());
+            ui.set_load_progress(snapshot.fraction().unwrap_or(0.0));
+            ui.set_load_text(SharedString::from(snapshot.message()));
+        });
+        tokio::time::sleep(PROGRESS_INTERVAL).await;
+    }
+}
+
+pub async fn initialize(
+    osu_exe_dir: String,
+    progress: Arc<IngestionProgress>,
+    ui: Weak<MainWindow>,
+) -> Result<()> {
+    let dir = osu_exe_dir.clone();
+    let task_progress = progress.clone();
+    let result = tokio::task::spawn_blocking(move || prepare_ratings(&dir, &task_progress)).await?;
+    if progress.is_cancelled() {
+        return Ok(());
+    }
+    let (mut data, report) = result?;
+    merge_ratings(&mut data, &imported_ratings());
+    let level = if report.skipped_count() > 0 {
+        Level::Warn
+    } else {
+        Level::Info
+    };
+    let summary = report.summary();
+    let players_list = {
         let mut scores = SCORES_DATA.lock().unwrap();
+        // 持有锁时再检查一次：新的读取在取消这次读取之后才会写入，不会被这次的结果覆盖
+        if progress.is_cancelled() {
+            return Ok(());
+        }
+        *INGESTION_REPORT.lock().unwrap() = report;
         *scores = data;
-        // let players_list = data.keys().map(SharedString::from).collect::<Vec<_>>();
-        scores
-            .keys()
-            .filter(|name| *name != "[All Players]" && *name != "[Recent 30]")
-            .map(SharedString::from)
-            .collect::<Vec<_>>()
+        collect_player_names(&scores)
     };
-    players_list.sort();
-    players_list.extend_from_slice(&[
-        SharedString::from("[All Players]"),
-        SharedString::from("[Recent 30]"),
-    ]);
+    notify(level, summary);
     ui.upgrade_in_event_loop(|ui| {
         ui.set_player_names(ModelRc::new(VecModel::from(players_list)));
-        ui.set_folder_select_enable(true);
-    })
-    .unwrap();
+    })?;
+    if progress.is_cancelled() {
+        return Ok(());
+    }
+    if let Err(e) = watch_osu_dir(osu_exe_dir, ui.clone()) {
+        notify(
+            Level::Warn,
+            tr!(
+                "无法监视osu!文件夹，新成绩需要重新选择文件夹才能读取: {}",
+                e
+            ),
+        );
+    }
     // Initialize the model with Recent 30
     update_player_b30(SharedString::from("[Recent 30]"), SCORES_DATA.clone(), ui).await;
     Ok(())
@@ -0,0 +1,370 @@
+use axum::Router;
+use axum::extract::State;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{Html, IntoResponse, Json, Response};
+use axum::routing::get;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::net::Ipv4Addr;
+use std::sync::{LazyLock, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_util::sync::CancellationToken;
+
+use crate::db::{B30Delta, OverallDefinition, RatingInfo, overall_definition};
+use crate::i18n::template_strings;
+
+pub const OVERLAY_PORT: u16 = 24060;
+const OVERLAY_HTML: &str = include_str!("../overlay/index.html");
+
+/// 推送给叠加层的事件，以JSON形式发送，`type`字段区分事件种类
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OverlayEvent {
+    State {
+        state: String,
+    },
+    /// 游玩过程中的事件：started, restarted, failed, quit, replay
+    Play {
+        event: String,
+    },
+    Score {
+        player: String,
+        title: String,
+        version: String,
+        mods: String,
+        judgement_num: [u32; 6],
+        accuracy: f64,
+        accuracy_rating: f64,
+        diff_const: f64,
+        rating: f64,
+        b30: OverlayB30Delta,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlayB30Delta {
+    pub enters_b30: bool,
+    pub rank: Option<usize>,
+    pub replaced_title: Option<String>,
+    pub replaced_rating: Option<f64>,
+    /// average_before和average_after的计算方式和成绩数量
+    #[serde(flatten)]
+    pub overall: OverallDefinition,
+    /// 按当前语言翻译的总Rating名称
+    pub overall_label: String,
+    pub average_before: f64,
+    pub average_after: f64,
+}
+
+impl OverlayEvent {
+    pub fn score(info: &RatingInfo, delta: &B30Delta) -> Self {
+        let beatmap_info = &info.map_info.info;
+        let overall = overall_definition();
+        OverlayEvent::Score {
+            player: info.score_info.player.clone(),
+            title: beatmap_info
+                .title_unicode
+                .clone()
+                .unwrap_or_else(|| beatmap_info.title.clone()),
+            version: beatmap_info.version.clone(),
+            mods: info.score_info.mods_text(),
+            judgement_num: info.score_info.judgement_num,
+            accuracy: info.score_info.accuracy,
+            accuracy_rating: info.score_info.accuracy_rating,
+            diff_const: info.diff_const,
+            rating: info.rating,
+            b30: OverlayB30Delta {
+                enters_b30: delta.enters_b30(),
+                rank: delta.new_rank,
+                replaced_title: delta.replaced.as_ref().map(|r| {
+                    r.map_info
+                        .info
+                        .title_unicode
+                        .clone()
+                        .unwrap_or_else(|| r.map_info.info.title.clone())
+                }),
+                replaced_rating: delta.replaced.as_ref().map(|r| r.rating),
+                overall,
+                overall_label: overall.short_label(),
+                average_before: delta.average_before,
+                average_after: delta.average_after,
+            },
+        }
+    }
+}
+
+static EVENTS: LazyLock<broadcast::Sender<OverlayEvent>> =
+    LazyLock::new(|| broadcast::channel(64).0);
+
+// 最近的游戏状态和成绩，新连接的客户端会先收到这些内容
+static LAST_STATE: LazyLock<Mutex<Option<OverlayEvent>>> = LazyLock::new(|| Mutex::new(None));
+static LAST_SCORE: LazyLock<Mutex<Option<OverlayEvent>>> = LazyLock::new(|| Mutex::new(None));
+
+/// 发布事件。服务器没有开启或者没有客户端时直接丢弃
+pub fn publish(event: OverlayEvent) {
+    match &event {
+        OverlayEvent::State { .. } => *LAST_STATE.lock().unwrap() = Some(event.clone()),
+        OverlayEvent::Score { .. } => *LAST_SCORE.lock().unwrap() = Some(event.clone()),
+        OverlayEvent::Play { .. } => {}
+    }
+    let _ = EVENTS.send(event);
+}
+
+/// `/api/state`的内容：最近的事件和按当前语言翻译的页面文本
+#[derive(Debug, Serialize)]
+struct OverlaySnapshot {
+    events: Vec<OverlayEvent>,
+    strings: HashMap<&'static str, String>,
+}
+
+fn snapshot() -> Vec<OverlayEvent> {
+    [
+        LAST_STATE.lock().unwrap().clone(),
+        LAST_SCORE.lock().unwrap().clone(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// 在127.0.0.1上开启叠加层服务器：`/`为OBS浏览器源使用的页面，`/ws`推送事件，
+/// `/api/state`返回最近的状态和成绩，以及页面使用的翻译文本
+pub async fn serve_overlay(port: u16, cancel: CancellationToken) -> io::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+    serve_on(listener, cancel).await
+}
+
+async fn serve_on(listener: TcpListener, cancel: CancellationToken) -> io::Result<()> {
+    let app = Router::new()
+        .route("/", get(|| async { Html(OVERLAY_HTML) }))
+        .route(
+            "/api/state",
+            get(|| async {
+                Json(OverlaySnapshot {
+                    events: snapshot(),
+                    strings: template_strings(),
+                })
+            }),
+        )
+        .route("/ws", get(ws_handler))
+        .with_state(cancel.clone());
+    // 监听在关闭信号后立即释放，已连接的客户端由handle_socket断开
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move { cancel.cancelled().await })
+        .await
+}
+
+/// 只接受本机页面的连接，其他网站的脚本不能通过浏览器读取事件。
+/// 没有Origin（OBS以外的本地客户端）或为null（本地文件）时放行
+fn is_allowed_origin(origin: &str) -> bool {
+    if origin.is_empty() || origin == "null" {
+        return true;
+    }
+    let Some(host) = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"))
+    else {
+        return false;
+    };
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => host,
+        _ => host,
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+async fn ws_handler(
+    State(cancel): State<CancellationToken>,
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+) -> Response {
+    let origin = headers.get(header::ORIGIN);
+    if origin.is_some_and(|origin| !origin.to_str().is_ok_and(is_allowed_origin)) {
+        log::warn!("Rejected overlay connection from origin {origin:?}");
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    ws.on_upgrade(move |socket| handle_socket(socket, cancel))
+        .into_response()
+}
+
+async fn handle_socket(mut socket: WebSocket, cancel: CancellationToken) {
+    let mut receiver = EVENTS.subscribe();
+    for event in snapshot() {
+        if send_event(&mut socket, &event).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            event = receiver.recv() => match event {
+                Ok(event) => {
+                    if send_event(&mut socket, &event).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => {}
+            },
+            _ = cancel.cancelled() => break,
+        }
+    }
+}
+
+async fn send_event(socket: &mut WebSocket, event: &OverlayEvent) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(event).unwrap_or_default();
+    socket.send(Message::Text(text.into())).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use std::net::SocketAddr;
+    use tokio_tungstenite::connect_async;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::{self, Message as ClientMessage};
+
+    async fn start_server() -> (SocketAddr, CancellationToken) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let cancel = CancellationToken::new();
+        tokio::spawn(serve_on(listener, cancel.clone()));
+        (addr, cancel)
+    }
+
+    fn event_json(message: ClientMessage) -> serde_json::Value {
+        serde_json::from_str(message.to_text().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn origin_check() {
+        for origin in [
+            "",
+            "null",
+            "http://localhost",
+            "http://127.0.0.1:24060",
+            "https://localhost:8080",
+            "http://[::1]:24060",
+        ] {
+            assert!(is_allowed_origin(origin), "{origin}");
+        }
+        for origin in [
+            "https://example.com",
+            "http://localhost.example.com",
+            "http://127.0.0.1.example.com:24060",
+            "file:///C:/overlay.html",
+            "localhost",
+        ] {
+            assert!(!is_allowed_origin(origin), "{origin}");
+        }
+    }
+
+    #[tokio::test]
+    async fn client_receives_snapshot_and_events() {
+        let (addr, cancel) = start_server().await;
+        publish(OverlayEvent::State {
+            state: "Playing".into(),
+        });
+        let (mut socket, _) = connect_async(format!("ws://{addr}/ws")).await.unwrap();
+
+        // 连接后先收到最近的状态
+        let first = event_json(socket.next().await.unwrap().unwrap());
+        assert_eq!(first["type"], "state");
+        assert_eq!(first["state"], "Playing");
+
+        publish(OverlayEvent::Play {
+            event: "restarted".into(),
+        });
+        loop {
+            let event = event_json(socket.next().await.unwrap().unwrap());
+            if event["type"] == "play" {
+                assert_eq!(event["event"], "restarted");
+                break;
+            }
+        }
+        cancel.cancel();
+    }
+
+    #[tokio::test]
+    async fn foreign_origin_is_rejected() {
+        let (addr, cancel) = start_server().await;
+        let mut request = format!("ws://{addr}/ws").into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("Origin", "https://example.com".parse().unwrap());
+        match connect_async(request).await {
+            Err(tungstenite::Error::Http(response)) => {
+                assert_eq!(response.status().as_u16(), StatusCode::FORBIDDEN.as_u16())
+            }
+            other => panic!("expected 403, got {:?}", other.err()),
+        }
+
+        let mut request = format!("ws://{addr}/ws").into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("Origin", format!("http://{addr}").parse().unwrap());
+        assert!(connect_async(request).await.is_ok());
+        cancel.cancel();
+    }
+
+    #[tokio::test]
+    async fn state_includes_translated_strings() {
+        let (addr, cancel) = start_server().await;
+        let request =
+            format!("GET /api/state HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        tokio::io::AsyncWriteExt::write_all(&mut stream, request.as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut stream, &mut response)
+            .await
+            .unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1;
+        let state: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert!(state["events"].is_array());
+        assert!(state["strings"]["overlay_waiting"].is_string());
+        cancel.cancel();
+    }
+
+    #[tokio::test]
+    async fn port_is_released_after_shutdown() {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let cancel = CancellationToken::new();
+        let server = tokio::spawn(serve_overlay(port, cancel.clone()));
+        // 等待服务器开始监听，连接的客户端不会阻止关闭
+        let (_socket, _) = loop {
+            if let Ok(connection) = connect_async(format!("ws://127.0.0.1:{port}/ws")).await {
+                break connection;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+        cancel.cancel();
+        tokio::time::timeout(std::time::Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        let cancel = CancellationToken::new();
+        let server = tokio::spawn(serve_overlay(port, cancel.clone()));
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!server.is_finished());
+        cancel.cancel();
+        server.await.unwrap().unwrap();
+    }
+}
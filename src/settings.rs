@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::{env, fs, io};
+
+use crate::db::{DEFAULT_SLOTS, DedupePolicy, OverallRating};
+use crate::graphx::ExportFormat;
+use crate::i18n::Language;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// 当前的设置，修改后调用`Settings::save`写回文件
+pub static SETTINGS: LazyLock<Mutex<Settings>> = LazyLock::new(|| Mutex::new(Settings::load()));
+
+/// 保存在程序目录下settings.json中的用户设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub language: Language,
+    /// 只有Ranked、Approved和Loved谱面的成绩计入B30
+    pub b30_ranked_only: bool,
+    /// 覆盖osu!.db中谱面状态的镜像文件（CSV）
+    pub status_mirror: Option<PathBuf>,
+    /// 成绩表的去重方式
+    pub dedupe: DedupePolicy,
+    /// 总Rating的计算方式
+    pub overall: OverallRating,
+    /// 计入总Rating的成绩数量
+    pub overall_slots: usize,
+    /// 导出成绩表和成绩历史时使用的格式
+    pub export_format: ExportFormat,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            language: Language::default(),
+            b30_ranked_only: false,
+            status_mirror: None,
+            dedupe: DedupePolicy::default(),
+            overall: OverallRating::default(),
+            overall_slots: DEFAULT_SLOTS,
+            export_format: ExportFormat::default(),
+        }
+    }
+}
+
+impl Settings {
+    fn path() -> io::Result<PathBuf> {
+        Ok(env::current_dir()?.join(SETTINGS_FILE))
+    }
+
+    /// 读取设置，文件不存在或格式错误时使用默认设置
+    fn load() -> Self {
+        let content = match Settings::path().and_then(fs::read_to_string) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Settings::default(),
+            Err(e) => {
+                log::warn!("Failed to read settings: {e}");
+                return Settings::default();
+            }
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("Failed to parse settings: {e}");
+            Settings::default()
+        })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let content = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(Settings::path()?, content)
+    }
+}
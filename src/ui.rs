@@ -1,10 +1,19 @@
 pub mod bs;
 pub mod callbacks;
+pub mod details;
+pub mod detector;
+pub mod history;
+pub mod memory;
+pub mod notify;
+pub mod picker;
+pub mod verify;
+pub mod watcher;
 
 use crate::MainWindow;
+use crate::ui::bs::RealtimeStatus;
 use slint::Weak;
-use std::sync::{Arc, Mutex};
-use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::watch;
 
 pub struct ScoreTileBase64 {
@@ -12,114 +21,68 @@ pub struct ScoreTileBase64 {
     pub base64_string: String,
 }
 
-#[allow(unused)]
-pub struct ThreadManager {
-    handle: Option<JoinHandle<()>>,
-    active: Arc<Mutex<bool>>,
-}
-
-impl ThreadManager {
-    pub fn new() -> Self {
-        Self {
-            handle: None,
-            active: Arc::new(Mutex::new(false)),
-        }
-    }
-
-    pub fn stop_thread(&mut self) {
-        if let Some(handle) = self.handle.take() {
-            *self.active.lock().unwrap() = false;
-            // handle.join().unwrap();
-            println!("try stop thread");
-            std::mem::drop(handle);
-        }
-    }
-
-    pub fn start_thread<F>(&mut self, f: F, ui: Weak<MainWindow>)
-    where
-        F: Fn(Weak<MainWindow>, Arc<Mutex<bool>>) + Send + 'static,
-    {
-        self.stop_thread();
-        let flag = self.active.clone();
-        let t = std::thread::spawn(move || {
-            f(ui, flag);
-        });
-        *self.active.lock().unwrap() = true;
-        self.handle = Some(t);
-    }
-
-    pub fn is_active(&self) -> bool {
-        *self.active.lock().unwrap() && self.handle.is_some()
-    }
-}
-
-impl Default for ThreadManager {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl Drop for ThreadManager {
-    fn drop(&mut self) {
-        self.stop_thread();
-    }
-}
-
+/// 管理实时读取任务。关闭时先通知任务自行退出，超时后才强制中止
 pub struct ThreadManagerAsync {
     handle: Option<tokio::task::JoinHandle<()>>,
-    active: Arc<watch::Sender<bool>>,
+    active: watch::Sender<bool>,
+    status: UnboundedSender<RealtimeStatus>,
 }
 
+/// 等待任务自行退出的最长时间
+const STOP_TIMEOUT: Duration = Duration::from_secs(3);
+
 impl ThreadManagerAsync {
-    pub fn new() -> Self {
+    pub fn new(status: UnboundedSender<RealtimeStatus>) -> Self {
         let (sender, _) = watch::channel(false);
         Self {
             handle: None,
-            active: Arc::new(sender),
+            active: sender,
+            status,
         }
     }
 
     pub async fn stop_thread(&mut self) {
-        if let Some(handle) = self.handle.take() {
-            self.active.send(false).unwrap();
+        self.active.send_replace(false);
+        if let Some(mut handle) = self.handle.take()
+            && tokio::time::timeout(STOP_TIMEOUT, &mut handle)
+                .await
+                .is_err()
+        {
+            log::warn!("Realtime task did not stop in time, aborting");
             handle.abort();
             let _ = handle.await;
+            let _ = self.status.send(RealtimeStatus::Stopped);
         }
     }
 
     pub async fn start_thread<F, Fut>(&mut self, f: F, ui: Weak<MainWindow>)
     where
         Fut: std::future::Future<Output = ()> + Send + 'static,
-        F: Fn(Weak<MainWindow>, Arc<watch::Receiver<bool>>) -> Fut + Send + 'static,
+        F: Fn(Weak<MainWindow>, watch::Receiver<bool>, UnboundedSender<RealtimeStatus>) -> Fut,
     {
         self.stop_thread().await;
+        // 先设置为true再启动，避免任务一开始就读到false而退出
+        self.active.send_replace(true);
         let flag_receiver = self.active.subscribe();
-        let t = tokio::spawn(f(ui, Arc::new(flag_receiver)));
-        self.active.send(true).unwrap();
+        let t = tokio::spawn(f(ui, flag_receiver, self.status.clone()));
         self.handle = Some(t);
     }
 
-    pub async fn is_active(&self) -> bool {
-        *self.active.borrow() && self.handle.is_some()
-    }
-}
-
-impl Default for ThreadManagerAsync {
-    fn default() -> Self {
-        Self::new()
+    pub fn is_active(&self) -> bool {
+        *self.active.borrow()
+            && self
+                .handle
+                .as_ref()
+                .is_some_and(|handle| !handle.is_finished())
     }
 }
 
 impl Drop for ThreadManagerAsync {
     fn drop(&mut self) {
-        // 注意：在Drop中不能直接调用async函数
-        // 可以使用block_on，但不推荐在生产代码中这样做
-        // tokio::runtime::Handle::current().block_on(async {
-        //     self.stop_thread().await;
-        // });
+        // Drop中不能等待，只通知退出并中止
+        self.active.send_replace(false);
         if let Some(handle) = self.handle.take() {
-            self.active.send(false).unwrap();
-            handle.abort(); // 只标记，不等待
+            handle.abort();
         }
     }
 }
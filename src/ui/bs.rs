@@ -1,372 +1,449 @@
 // This is a part for wiping the stinky asses of dumb crate providers like rosu-memory-lib
-use crate::db::{PlayRecord, RatingInfo, RatingMapInfo, calc_mod_rating};
+use crate::db::{
+    LiveEstimate, PlayRecord, RatingInfo, RatingMapInfo, b30_entry_threshold, beatmap_status,
+    calc_sr_rating, estimate_live, overall_definition, play_sr, player_list_name,
+    record_session_score,
+};
 use crate::graphx::generate_single_card_pixmap;
-use crate::{MainWindow, ScoreTileData};
-use anyhow::{Result, bail};
+use crate::overlay::{OverlayEvent, publish};
+use crate::tr;
+use crate::ui::callbacks::{card_tile, update_b30_delta, update_session_summary};
+use crate::ui::detector::{RealtimeEvent, ScoreDetector};
+use crate::ui::memory::{BeatmapData, GameMemorySource, ResultScreenData, StableMemorySource};
+use crate::ui::notify::notify;
+use crate::{MainWindow, REALTIME_DATA, SCORES_DATA, ScoreTileData};
 use chrono::Utc;
-use mania_converter::osu_func::{OsuDataV128, calculate_from_data};
+use log::Level;
+use mania_converter::BeatMapInfo;
 use osu_db::ModSet;
 use rosu_mem::process::{Process, ProcessTraits};
-use rosu_memory_lib::common::GameState;
-use rosu_memory_lib::common::stable::memory::game_state;
-use rosu_memory_lib::reader::beatmap::BeatmapReader;
-use rosu_memory_lib::reader::common::OsuClientKind;
-use rosu_memory_lib::reader::gameplay::GameplayReader;
-use rosu_memory_lib::reader::resultscreen::ResultScreenReader;
+use rosu_memory_lib::common::GameMode;
 use rosu_memory_lib::reader::structs::{State, StaticAddresses};
-use slint::{
-    ComponentHandle, Image, Model, Rgba8Pixel, SharedPixelBuffer, SharedString, VecModel, Weak,
-};
-use std::sync::{Arc, Mutex};
+use slint::{ComponentHandle, Model, SharedString, VecModel, Weak};
 use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
 
 // The original fking `init_loop()` just goes into a dead loop if osu!.exe is not booted
-// And the thread generated whether by std::thread or tokio just won't fking terminate
-// So I have to implement a version where I can control the stop signal myself
-// Will generate an async version later
-#[allow(dead_code)]
-fn init_loop_with_flag(sleep_duration: u64, flag: Arc<Mutex<bool>>) -> Result<(State, Process)> {
+// So this version checks the stop signal between every attempt
+/// 等待osu!启动并读取静态地址，期间关闭实时模式时返回None
+async fn init_loop_async(
+    sleep_duration: u64,
+    flag: &mut watch::Receiver<bool>,
+) -> Option<(State, Process)> {
     let mut state = State {
         addresses: StaticAddresses::default(),
     };
 
     loop {
-        if !(*flag.lock().unwrap()) {
-            bail!("Force exit");
+        if !*flag.borrow() {
+            return None;
         }
         if let Some(v) = init_loop_inner(&mut state, sleep_duration) {
-            return Ok((state, v));
+            return Some((state, v));
+        }
+        if !wait_or_stop(sleep_duration, flag).await {
+            return None;
         }
-        std::thread::sleep(Duration::from_millis(sleep_duration));
     }
 }
 
-#[allow(dead_code)]
-async fn init_loop_async(sleep_duration: u64) -> (State, Process) {
-    let mut state = State {
-        addresses: StaticAddresses::default(),
-    };
-
-    loop {
-        if let Some(v) = init_loop_inner(&mut state, sleep_duration) {
-            return (state, v);
+/// 等待一段时间，期间关闭实时模式时立即返回false
+async fn wait_or_stop(sleep_duration: u64, flag: &mut watch::Receiver<bool>) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(Duration::from_millis(sleep_duration)) => {}
+        changed = flag.changed() => {
+            if changed.is_err() {
+                return false;
+            }
         }
-        tokio::time::sleep(Duration::from_millis(sleep_duration)).await;
     }
+    *flag.borrow()
 }
 
 fn init_loop_inner(state: &mut State, sleep_duration: u64) -> Option<Process> {
     match Process::initialize("osu!.exe", &["umu-run", "waitforexitandrun"]) {
         Ok(p) => {
-            println!("Found process, pid - {}", p.pid);
-            println!("Reading static signatures...");
+            log::info!("Found process, pid - {}", p.pid);
+            log::debug!("Reading static signatures...");
             match StaticAddresses::new(&p) {
                 Ok(v) => {
                     state.addresses = v;
-                    println!("Static addresses read successfully");
+                    log::info!("Static addresses read successfully");
                     return Some(p);
                 }
                 Err(e) => match e {
                     rosu_memory_lib::error::Error::MemoryRead(msg) => {
                         if msg.contains("Process not found") {
-                            println!("Process not found, sleeping for {sleep_duration}ms");
+                            log::debug!("Process not found, sleeping for {sleep_duration}ms");
                         }
                         #[cfg(target_os = "windows")]
                         if msg.contains("OS error") {
-                            println!("OS error, sleeping for {sleep_duration}ms");
+                            log::debug!("OS error, sleeping for {sleep_duration}ms");
                         }
-                        println!("Unknown error, sleeping for {sleep_duration}ms");
+                        log::debug!("Unknown error, sleeping for {sleep_duration}ms");
                     }
                     _ => {
-                        println!("Unknown error, sleeping for {sleep_duration}ms");
+                        log::debug!("Unknown error, sleeping for {sleep_duration}ms");
                     }
                 },
             }
         }
         Err(_) => {
-            println!("Unknown process error, sleeping for {sleep_duration}ms");
+            log::debug!("Unknown process error, sleeping for {sleep_duration}ms");
         }
     }
     None
 }
 
+/// 实时读取任务的状态，通过channel发送给UI
+#[derive(Debug, Clone)]
+pub enum RealtimeStatus {
+    /// 正在等待osu!启动
+    Searching,
+    Attached,
+    /// osu!已经关闭，重新等待启动
+    ProcessLost,
+    Stopped,
+    /// 检测到的游玩事件
+    Message(String),
+}
+
+impl RealtimeStatus {
+    pub fn message(&self) -> String {
+        match self {
+            RealtimeStatus::Searching => tr!("正在尝试读取osu内存"),
+            RealtimeStatus::Attached => tr!("已读取osu内存"),
+            RealtimeStatus::ProcessLost => tr!("osu!已关闭，等待重新启动"),
+            RealtimeStatus::Stopped => tr!("实时模式已关闭"),
+            RealtimeStatus::Message(message) => message.clone(),
+        }
+    }
+}
+
+/// 实时读取任务。osu!关闭后会重新等待启动，直到`flag`变为false
 pub async fn update_realtime_async(
     ui_handle: Weak<MainWindow>,
-    flag: Arc<tokio::sync::watch::Receiver<bool>>,
+    mut flag: watch::Receiver<bool>,
+    status: UnboundedSender<RealtimeStatus>,
 ) {
-    println!("正在尝试读取osu内存");
-    ui_handle
-        .upgrade_in_event_loop(|ui| {
-            ui.set_test_content(SharedString::from("正在尝试读取osu内存"));
-        })
-        .unwrap();
+    'session: loop {
+        log::info!("Waiting for osu! process");
+        let _ = status.send(RealtimeStatus::Searching);
+        let Some((state, process)) = init_loop_async(500, &mut flag).await else {
+            break;
+        };
+        let mut source = StableMemorySource::new(state, process);
 
-    let (mut state, process) = init_loop_async(500).await;
+        log::info!("Attached to osu! process");
+        let _ = status.send(RealtimeStatus::Attached);
 
-    println!("已读取osu内存");
-    ui_handle
-        .upgrade_in_event_loop(|ui| {
-            ui.set_test_content(SharedString::from("已读取osu内存"));
-        })
-        .unwrap();
+        let mut detector = ScoreDetector::new();
+        let mut live_play = None;
 
-    let mut new_score_flag = false;
-    let mut mods = ModSet::from_bits(0);
-    let mut prev_state = GameState::Unknown;
-
-    loop {
-        {
-            if !*flag.borrow() {
-                println!("exit loop");
-                break;
+        loop {
+            // 谱面解析和星级计算是阻塞的，不能占用异步运行时
+            tokio::task::block_in_place(|| {
+                update_realtime_inner(
+                    &mut source,
+                    &mut detector,
+                    &mut live_play,
+                    &status,
+                    ui_handle.clone(),
+                )
+            });
+            if !source.is_alive() {
+                log::info!("osu! process lost");
+                let _ = status.send(RealtimeStatus::ProcessLost);
+                continue 'session;
+            }
+            if !wait_or_stop(500, &mut flag).await {
+                break 'session;
             }
         }
-        update_realtime_inner(
-            &process,
-            &mut state,
-            &mut prev_state,
-            &mut new_score_flag,
-            &mut mods,
-            ui_handle.clone(),
-        );
-        tokio::time::sleep(Duration::from_millis(500)).await;
     }
+    log::info!("Realtime task stopped");
+    let _ = status.send(RealtimeStatus::Stopped);
 }
 
-pub fn update_realtime(ui_handle: Weak<MainWindow>, flag: Arc<Mutex<bool>>) {
-    println!("正在尝试读取osu内存");
-    ui_handle
-        .upgrade_in_event_loop(|ui| {
-            ui.set_test_content(SharedString::from("正在尝试读取osu内存"));
-        })
-        .unwrap();
-
-    // init_loop is a total BS which does a loop that never ends, so I had to do the same function myself...
-    let (mut state, process) = match init_loop_with_flag(500, flag.clone()) {
-        Ok((state, process)) => (state, process),
-        Err(e) => {
-            println!("Error: {e}");
-            return;
-        }
-    };
+/// 正在进行的游玩，用于实时估算Rating
+struct LivePlay {
+    /// 已经考虑了mod的星级
+    sr: f64,
+    total_judgements: u32,
+    b30_threshold: f64,
+}
 
-    println!("已读取osu内存");
-    ui_handle
-        .upgrade_in_event_loop(|ui| {
-            ui.set_test_content(SharedString::from("已读取osu内存"));
+impl LivePlay {
+    fn new(beatmap: BeatmapData, mods: ModSet) -> Option<Self> {
+        let (info, sr) = play_sr(&beatmap.md5, &beatmap.path, mods)?;
+        let b30_threshold = current_player()
+            .and_then(|player| {
+                SCORES_DATA
+                    .lock()
+                    .unwrap()
+                    .get(&player_list_name(player))
+                    .map(|scores| b30_entry_threshold(scores, &beatmap.md5, &info))
+            })
+            .unwrap_or(0.0);
+        Some(LivePlay {
+            sr,
+            total_judgements: total_judgements(&info, mods),
+            b30_threshold,
         })
-        .unwrap();
+    }
 
-    let mut new_score_flag = false;
-    let mut mods = ModSet::from_bits(0);
-    let mut prev_state = GameState::Unknown;
+    fn estimate(&self, hits: [u32; 6]) -> LiveEstimate {
+        estimate_live(self.sr, hits, self.total_judgements, self.b30_threshold)
+    }
+}
 
-    loop {
-        {
-            if !*flag.lock().unwrap() {
-                println!("exit loop");
-                break;
-            }
-        }
-        update_realtime_inner(
-            &process,
-            &mut state,
-            &mut prev_state,
-            &mut new_score_flag,
-            &mut mods,
-            ui_handle.clone(),
-        );
-        std::thread::sleep(Duration::from_millis(500));
+/// 整张谱面的判定数量。ScoreV2中面条的头尾分别判定，ScoreV1中每个面条只有一个判定
+fn total_judgements(info: &BeatMapInfo, mods: ModSet) -> u32 {
+    let ln_judgements = if mods.bits() & 0x2000_0000 != 0 { 2 } else { 1 };
+    (info.note_count + ln_judgements * info.ln_count) as u32
+}
+
+/// 游玩中无法读取玩家名，使用本次会话或者最近一次游玩的玩家
+fn current_player() -> Option<String> {
+    if let Some(info) = REALTIME_DATA.lock().unwrap().last() {
+        return Some(info.score_info.player.clone());
     }
+    SCORES_DATA
+        .lock()
+        .unwrap()
+        .get("[Recent 30]")?
+        .first()
+        .map(|info| info.score_info.player.clone())
+}
+
+fn format_live_estimate(estimate: &LiveEstimate) -> String {
+    let slots = overall_definition().slots;
+    let needed = match estimate.needed_accuracy {
+        Some(acc) if acc <= 0.0 => tr!("已确保进入B{}", slots),
+        Some(acc) => tr!("进入B{}需要剩余Acc {}%", slots, format!("{acc:.02}")),
+        None => tr!("无法进入B{}", slots),
+    };
+    tr!(
+        "实时Acc {}%  定数 {}  预计Rating {}  B{}门槛 {}  {}",
+        format!("{:.02}", estimate.accuracy_rating),
+        format!("{:.02}", estimate.diff_const),
+        format!("{:.02}", estimate.rating),
+        slots,
+        format!("{:.02}", estimate.b30_threshold),
+        needed
+    )
+}
+
+fn set_live_estimate(text: String, ui_handle: &Weak<MainWindow>) {
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        ui.set_live_estimate(SharedString::from(text));
+    });
 }
 
 fn update_realtime_inner(
-    process: &Process,
-    state: &mut State,
-    prev_state: &mut GameState,
-    new_score_flag: &mut bool,
-    mods: &mut ModSet,
+    source: &mut impl GameMemorySource,
+    detector: &mut ScoreDetector,
+    live_play: &mut Option<LivePlay>,
+    status: &UnboundedSender<RealtimeStatus>,
     ui_handle: Weak<MainWindow>,
 ) {
-    let gamestate = game_state(process, state).unwrap_or(GameState::Unknown);
-    if gamestate != *prev_state {
-        let state_string = format!("当前游戏状态: {gamestate:?}");
-        ui_handle
-            .upgrade_in_event_loop(|ui| {
-                ui.set_test_content(SharedString::from(state_string));
-            })
-            .unwrap();
-        *prev_state = gamestate;
-    }
-
-    match game_state(process, state) {
-        Ok(GameState::ResultScreen) => {
-            if *new_score_flag {
-                println!("Reading result screen");
-                let mut resultscreen_reader =
-                    ResultScreenReader::new(process, state, OsuClientKind::Stable);
-
-                match resultscreen_reader.mode() {
-                    Ok(rosu_memory_lib::common::GameMode::Mania) => {}
-                    Ok(rosu_memory_lib::common::GameMode::Unknown) => {
-                        println!("Read fails, retrying...");
-                        return;
-                    }
-                    Ok(mode) => {
-                        println!("Unsupported game mode: {mode:?}");
-                        *new_score_flag = false;
-                        return;
-                    }
-                    Err(e) => {
-                        println!("Error: {e}");
-                        return;
-                    }
+    for event in detector.poll(source) {
+        let message = match event {
+            RealtimeEvent::StateChanged(gamestate) => {
+                publish(OverlayEvent::State {
+                    state: format!("{gamestate:?}"),
+                });
+                tr!("当前游戏状态: {}", format!("{gamestate:?}"))
+            }
+            RealtimeEvent::PlayStarted { mods, beatmap } => {
+                publish(OverlayEvent::Play {
+                    event: "started".into(),
+                });
+                *live_play = beatmap.and_then(|beatmap| LivePlay::new(beatmap, mods));
+                let text = live_play
+                    .as_ref()
+                    .map(|play| format_live_estimate(&play.estimate([0; 6])))
+                    .unwrap_or_default();
+                set_live_estimate(text, &ui_handle);
+                tr!("开始游玩")
+            }
+            RealtimeEvent::PlayProgress { hits } => {
+                if let Some(play) = live_play {
+                    set_live_estimate(format_live_estimate(&play.estimate(hits)), &ui_handle);
                 }
-                let (marv, perf, great, good, bad, miss) = (
-                    resultscreen_reader.hits_geki().unwrap_or(0) as u32,
-                    resultscreen_reader.hits_300().unwrap_or(0) as u32,
-                    resultscreen_reader.hits_katu().unwrap_or(0) as u32,
-                    resultscreen_reader.hits_100().unwrap_or(0) as u32,
-                    resultscreen_reader.hits_50().unwrap_or(0) as u32,
-                    resultscreen_reader.hits_miss().unwrap_or(0) as u32,
-                );
-                let total = marv + perf + great + good + bad + miss;
-
-                let accuracy_rating = (310.0 * marv as f64
-                    + 300.0 * perf as f64
-                    + 200.0 * great as f64
-                    + 100.0 * good as f64
-                    + 50.0 * bad as f64)
-                    / (3.1 * total as f64);
-                let player = resultscreen_reader.username().unwrap_or_default();
-                // The memory lib can't detect V2
-                let accuracy = if mods.bits() & 0x2000_0000 != 0 {
-                    (305.0 * marv as f64
-                        + 300.0 * perf as f64
-                        + 200.0 * great as f64
-                        + 100.0 * good as f64
-                        + 50.0 * bad as f64)
-                        / (3.05 * total as f64)
-                } else {
-                    resultscreen_reader.accuracy().unwrap_or(0.0)
-                };
-
-                println!("Reading beatmap path");
-                let mut beatmap_reader =
-                    BeatmapReader::new(process, state, OsuClientKind::Stable).unwrap();
-                if beatmap_reader.path().is_err() {
-                    println!("Read fails, retrying...");
-                    return;
+                continue;
+            }
+            RealtimeEvent::PlayRestarted => {
+                publish(OverlayEvent::Play {
+                    event: "restarted".into(),
+                });
+                tr!("重新开始")
+            }
+            RealtimeEvent::PlayFailed {
+                hits,
+                mods,
+                beatmap,
+            } => {
+                publish(OverlayEvent::Play {
+                    event: "failed".into(),
+                });
+                // 失败的成绩不会写入scores.db，剩余的物件按Miss计入后只记录在实时模式中
+                if let Some(beatmap) = beatmap
+                    && let Some(play) = live_play.as_ref()
+                    && let Some(player) = current_player()
+                    && let Some(mut rating_info) = build_rating_info(
+                        failed_result(player, hits, play.total_judgements),
+                        beatmap,
+                        mods,
+                    )
+                {
+                    rating_info.score_info.failed = true;
+                    add_realtime_score(rating_info, ui_handle.clone());
                 }
-                let path = beatmap_reader.path().unwrap();
-                println!("Parsing beatmap");
-                let mut beatmap = match OsuDataV128::from_file(path.to_str().unwrap()) {
-                    Ok(beatmap) => beatmap.to_legacy(),
-                    Err(e) => {
-                        // Often fails if not mania
-                        println!("Error: {e}");
-                        return;
-                    }
-                };
-
-                if beatmap.misc.circle_size != 6 || mods.contains(osu_db::Mod::Random) {
-                    // Not 6K
-                    *new_score_flag = false;
-                    return;
+                tr!("游玩失败，剩余的物件按Miss记录，不计入B30")
+            }
+            RealtimeEvent::PlayQuit => {
+                publish(OverlayEvent::Play {
+                    event: "quit".into(),
+                });
+                tr!("退出游玩")
+            }
+            RealtimeEvent::ReplayViewed => {
+                publish(OverlayEvent::Play {
+                    event: "replay".into(),
+                });
+                tr!("正在观看回放，不计入成绩")
+            }
+            RealtimeEvent::ScoreSubmitted {
+                result,
+                beatmap,
+                mods,
+            } => {
+                if let Some(rating_info) = build_rating_info(result, beatmap, mods) {
+                    add_realtime_score(rating_info, ui_handle.clone());
                 }
+                continue;
+            }
+        };
+        let _ = status.send(RealtimeStatus::Message(message));
+    }
+}
 
-                let info = beatmap.to_beatmap_info(true);
-                let md5 = beatmap_reader.md5().unwrap();
-                let map_info = RatingMapInfo {
-                    hash: md5,
-                    path,
-                    info,
-                };
-
-                let timestamp = Utc::now();
-                let play_record = PlayRecord {
-                    player,
-                    mods: *mods,
-                    accuracy,
-                    accuracy_rating,
-                    judgement_num: [marv, perf, great, good, bad, miss],
-                    timestamp,
-                };
+/// 失败时的判定数量，没有判定的物件计为Miss
+fn failed_result(player: String, hits: [u32; 6], total_judgements: u32) -> ResultScreenData {
+    let mut judgement_num = hits;
+    judgement_num[5] += total_judgements.saturating_sub(hits.iter().sum());
+    let [marv, perf, great, good, bad, miss] = judgement_num;
+    let total = (marv + perf + great + good + bad + miss).max(1);
+    let accuracy = (300.0 * (marv + perf) as f64
+        + 200.0 * great as f64
+        + 100.0 * good as f64
+        + 50.0 * bad as f64)
+        / (3.0 * total as f64);
+    ResultScreenData {
+        mode: GameMode::Mania,
+        username: player,
+        judgement_num,
+        accuracy,
+    }
+}
 
-                println!("Calculating rating");
-                if mods.contains(osu_db::Mod::HardRock) || mods.contains(osu_db::Mod::Easy) {
-                    let original_od = beatmap.misc.od;
-                    let window = 64.5 - (original_od * 3.0).ceil();
-                    let new_window = if mods.contains(osu_db::Mod::HardRock) {
-                        window / 1.4
-                    } else {
-                        window * 1.4
-                    };
-                    let new_od = (64.5 - new_window) / 3.0;
-                    beatmap.misc.od = new_od;
-                }
-                let sr = calculate_from_data(&beatmap, 1.0).unwrap_or(0.0);
+/// 由结算界面的判定和当前谱面计算Rating，不是6K或者开启了Random时返回None
+fn build_rating_info(
+    result: ResultScreenData,
+    beatmap_data: BeatmapData,
+    mods: ModSet,
+) -> Option<RatingInfo> {
+    let [marv, perf, great, good, bad, miss] = result.judgement_num;
+    let total = marv + perf + great + good + bad + miss;
 
-                let sr_ht = if mods.contains(osu_db::Mod::HalfTime) {
-                    calculate_from_data(&beatmap, 0.75).unwrap_or(0.0)
-                } else {
-                    0.0
-                };
+    let accuracy_rating = (310.0 * marv as f64
+        + 300.0 * perf as f64
+        + 200.0 * great as f64
+        + 100.0 * good as f64
+        + 50.0 * bad as f64)
+        / (3.1 * total as f64);
+    // The memory lib can't detect V2
+    let accuracy = if mods.bits() & 0x2000_0000 != 0 {
+        (305.0 * marv as f64
+            + 300.0 * perf as f64
+            + 200.0 * great as f64
+            + 100.0 * good as f64
+            + 50.0 * bad as f64)
+            / (3.05 * total as f64)
+    } else {
+        result.accuracy
+    };
 
-                let sr_dt = if mods.contains(osu_db::Mod::DoubleTime) {
-                    calculate_from_data(&beatmap, 1.5).unwrap_or(0.0)
-                } else {
-                    0.0
-                };
+    let path = beatmap_data.path;
+    let (info, sr) = play_sr(&beatmap_data.md5, &path, mods)?;
+    let map_info = RatingMapInfo {
+        status: beatmap_status(&beatmap_data.md5),
+        hash: beatmap_data.md5,
+        path,
+        info,
+    };
 
-                let (diff_const, rating) =
-                    calc_mod_rating(*mods, (sr_ht, sr, sr_dt), accuracy_rating);
+    let timestamp = Utc::now();
+    let play_record = PlayRecord {
+        player: result.username,
+        mods,
+        accuracy,
+        accuracy_rating,
+        judgement_num: result.judgement_num,
+        timestamp,
+        failed: false,
+    };
 
-                let rating_info = RatingInfo {
-                    map_info,
-                    score_info: play_record,
-                    diff_const,
-                    rating,
-                };
+    let (diff_const, rating) = calc_sr_rating(sr, accuracy_rating);
 
-                println!("Updating UI");
+    Some(RatingInfo {
+        map_info,
+        score_info: play_record,
+        diff_const,
+        rating,
+    })
+}
 
-                ui_handle
-                    .upgrade_in_event_loop(move |ui| {
-                        println!("UI Function starts");
+/// 记录新成绩，并在实时模式列表的最前面插入卡片
+fn add_realtime_score(rating_info: RatingInfo, ui_handle: Weak<MainWindow>) {
+    if let Err(e) = record_session_score(&rating_info) {
+        notify(Level::Error, tr!("成绩记录文件写入失败: {}", e));
+    }
+    // 卡片的序号为成绩在REALTIME_DATA中的位置，与realtime_card一致
+    let index = {
+        let mut realtime = REALTIME_DATA.lock().unwrap();
+        realtime.push(rating_info.clone());
+        realtime.len() - 1
+    };
+    update_session_summary(ui_handle.clone());
+    // 失败的游玩不计入B30，也不能加入成绩表
+    if !rating_info.score_info.failed {
+        update_b30_delta(rating_info.clone(), ui_handle.clone());
+    }
 
-                        let realtime_tiles = ui.get_realtime_tiles();
-                        let index = realtime_tiles.row_count();
-                        let realtime_tiles_vec = realtime_tiles
-                            .as_any()
-                            .downcast_ref::<VecModel<ScoreTileData>>()
-                            .unwrap();
-                        let new_pixmap = generate_single_card_pixmap(index, &rating_info).unwrap();
-                        let pixel_buffer = SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(
-                            new_pixmap.data(),
-                            new_pixmap.width(),
-                            new_pixmap.height(),
-                        );
-                        let new_data = ScoreTileData {
-                            image: Image::from_rgba8(pixel_buffer),
-                            index: index as i32,
-                        };
-                        realtime_tiles_vec.insert(0, new_data);
-                        ui.window().request_redraw();
-                        println!("UI Function ends");
-                    })
-                    .unwrap();
-                println!("New score added");
+    let result = ui_handle.upgrade_in_event_loop(move |ui| {
+        let realtime_tiles = ui.get_realtime_tiles();
+        let Some(realtime_tiles_vec) = realtime_tiles
+            .as_any()
+            .downcast_ref::<VecModel<ScoreTileData>>()
+        else {
+            return;
+        };
+        let new_pixmap = match generate_single_card_pixmap(index, &rating_info) {
+            Ok(pixmap) => Some(pixmap),
+            Err(e) => {
+                notify(Level::Warn, tr!("卡片生成失败: {}", e));
+                None
             }
-            *new_score_flag = false;
-        }
-        Ok(GameState::Playing) => {
-            let mut gameplay_reader = GameplayReader::new(process, state, OsuClientKind::Stable);
-            *mods = osu_db::ModSet::from_bits(gameplay_reader.mods().unwrap_or(0));
-            *new_score_flag = true;
-        }
-        _ => {
-            *new_score_flag = false;
-        }
+        };
+        realtime_tiles_vec.insert(0, card_tile(index, new_pixmap.as_ref()));
+        ui.window().request_redraw();
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to update UI: {e}");
+        return;
     }
+    log::info!("New score added");
 }
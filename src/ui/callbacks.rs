@@ -1,63 +1,144 @@
-use crate::db::RatingInfo;
-use crate::graphx::{export_info, generate_single_card_pixmap};
+use crate::db::{
+    ExportManifest, IMPORTED_RATINGS, RatingInfo, compute_b30_delta, counts_for_b30,
+    default_dialog_location, load_journal, merge_ratings, overall_definition, overall_rating,
+    player_list_name, removed_duplicates, session_dir, summarize_session, table_size,
+};
+use crate::error::{AppError, AppResult};
+use crate::graphx::{
+    ExportFormat, export_format, export_info, export_pdf, export_session_summary,
+    generate_single_card_pixmap,
+};
+use crate::overlay::{OverlayEvent, publish};
+use crate::tr;
 use crate::ui::ScoreTileBase64;
-use crate::{MainWindow, ScoreTileData};
+use crate::ui::notify::notify;
+use crate::{MainWindow, PENDING_SCORE, REALTIME_DATA, SCORES_DATA, ScoreTileData};
 use arboard::Clipboard;
 use base64::prelude::*;
+use log::Level;
 use native_dialog::{DialogBuilder, MessageLevel};
 use rayon::prelude::*;
 use resvg::tiny_skia::{IntSize, Pixmap};
-use slint::{Image, Model, ModelRc, Rgba8Pixel, SharedPixelBuffer, SharedString, VecModel, Weak};
-use std::collections::HashMap;
+use slint::{
+    ComponentHandle, Image, Model, ModelRc, Rgba8Pixel, SharedPixelBuffer, SharedString, VecModel,
+    Weak,
+};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use tokio::task::spawn_blocking;
 
+/// 下拉框中的玩家列表：玩家名称按字母排序，最后是[All Players]和[Recent 30]
+pub fn collect_player_names(scores: &HashMap<String, Vec<RatingInfo>>) -> Vec<SharedString> {
+    let mut players_list = scores
+        .keys()
+        .filter(|name| *name != "[All Players]" && *name != "[Recent 30]")
+        .map(SharedString::from)
+        .collect::<Vec<_>>();
+    players_list.sort();
+    players_list.extend_from_slice(&[
+        SharedString::from("[All Players]"),
+        SharedString::from("[Recent 30]"),
+    ]);
+    players_list
+}
+
+/// SCORES_DATA变化后原地更新下拉框的玩家列表，若当前玩家的成绩发生了变化则重新生成卡片
+pub fn sync_player_models(changed_players: HashSet<String>, ui_handle: Weak<MainWindow>) {
+    let players_list = collect_player_names(&SCORES_DATA.lock().unwrap());
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        let player_names = ui.get_player_names();
+        match player_names
+            .as_any()
+            .downcast_ref::<VecModel<SharedString>>()
+        {
+            Some(names_vec) => {
+                if !names_vec.iter().eq(players_list.iter().cloned()) {
+                    names_vec.set_vec(players_list);
+                }
+            }
+            None => ui.set_player_names(ModelRc::new(VecModel::from(players_list))),
+        }
+
+        let player_name = ui.get_current_player_name();
+        if changed_players.contains(player_name.as_str()) {
+            ui.set_export_enable(false);
+            ui.set_text_content(SharedString::from(tr!("检测到新成绩，正在刷新...")));
+            tokio::spawn(update_player_b30(
+                player_name,
+                SCORES_DATA.clone(),
+                ui.as_weak(),
+            ));
+        }
+    });
+}
+
 pub async fn update_player_b30(
     player_name: SharedString,
     rating_selection: Arc<Mutex<HashMap<String, Vec<RatingInfo>>>>,
     ui_handle: Weak<MainWindow>,
 ) {
-    let player_ratings = {
+    let (player_ratings, duplicates) = {
         let r = rating_selection.lock().unwrap();
-        r.get(player_name.as_str()).cloned()
+        (
+            r.get(player_name.as_str()).cloned(),
+            removed_duplicates(player_name.as_str(), &r),
+        )
     };
     if let Some(rating) = player_ratings {
-        let len = rating.len().min(30);
+        let len = rating.len().min(table_size());
         let slice = rating[0..len].to_vec();
-        let pixmaps = spawn_blocking(move || {
-            slice
+        // 重复的成绩放在备选区，序号接在成绩表之后
+        let offset = rating.len();
+        let rendered = spawn_blocking(move || {
+            let pixmaps = slice
+                .par_iter()
+                .enumerate()
+                .map(|(i, r)| (i, render_card(i, r)))
+                .collect::<Vec<_>>();
+            let duplicate_pixmaps = duplicates
                 .par_iter()
                 .enumerate()
-                .filter_map(|(i, r)| generate_single_card_pixmap(i, r).ok())
-                .collect::<Vec<_>>()
+                .map(|(j, r)| (offset + j, render_card(offset + j, r)))
+                .collect::<Vec<_>>();
+            (pixmaps, duplicate_pixmaps)
         })
-        .await
-        .unwrap();
+        .await;
+        let (pixmaps, duplicate_pixmaps) = match rendered {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                notify(Level::Error, tr!("卡片生成失败: {}", e));
+                return;
+            }
+        };
 
-        ui_handle
-            .upgrade_in_event_loop(move |ui| {
-                let tiles: Vec<ScoreTileData> = pixmaps
-                    .iter()
-                    .enumerate()
-                    .map(|(i, pixmap)| {
-                        let pixel_buffer = SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(
-                            pixmap.data(),
-                            pixmap.width(),
-                            pixmap.height(),
-                        );
-                        ScoreTileData {
-                            image: Image::from_rgba8(pixel_buffer),
-                            index: i as i32,
-                        }
-                    })
-                    .collect();
-                let score_tiles_model = ModelRc::new(VecModel::from(tiles));
-                ui.set_score_tiles(score_tiles_model);
-                ui.set_removed_tiles(ModelRc::new(VecModel::from(Vec::new())));
-                ui.set_text_content(SharedString::from(""));
-                ui.set_export_enable(true);
-            })
-            .unwrap();
+        let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+            let tiles: Vec<ScoreTileData> = pixmaps
+                .iter()
+                .map(|(i, pixmap)| card_tile(*i, pixmap.as_ref()))
+                .collect();
+            let removed_tiles: Vec<ScoreTileData> = duplicate_pixmaps
+                .iter()
+                .map(|(i, pixmap)| card_tile(*i, pixmap.as_ref()))
+                .collect();
+            let score_tiles_model = ModelRc::new(VecModel::from(tiles));
+            ui.set_score_tiles(score_tiles_model);
+            ui.set_removed_tiles(ModelRc::new(VecModel::from(removed_tiles)));
+            ui.set_text_content(SharedString::from(""));
+            ui.set_export_enable(true);
+            refresh_overall_rating(&ui);
+        });
+    }
+}
+
+/// 生成单张卡片，失败时通知用户并返回None
+fn render_card(i: usize, info: &RatingInfo) -> Option<resvg::tiny_skia::Pixmap> {
+    match generate_single_card_pixmap(i, info) {
+        Ok(pixmap) => Some(pixmap),
+        Err(e) => {
+            notify(Level::Warn, tr!("#{} 卡片生成失败: {}", i + 1, e));
+            None
+        }
     }
 }
 
@@ -73,57 +154,52 @@ pub async fn remove_tile(
     };
     if let Some(ratings) = player_ratings {
         let ratings = ratings.clone();
-        ui_handle
-            .upgrade_in_event_loop(move |ui| {
-                let score_tiles = ui.get_score_tiles();
-                let score_tiles_vec = score_tiles
+        let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+            let score_tiles = ui.get_score_tiles();
+            let removed_tiles = ui.get_removed_tiles();
+            let (Some(score_tiles_vec), Some(removed_tiles_vec)) = (
+                score_tiles
                     .as_any()
-                    .downcast_ref::<VecModel<ScoreTileData>>()
-                    .unwrap();
-                if score_tiles_vec.row_count() == 0 {
-                    return;
-                }
-
-                let removed_tiles = ui.get_removed_tiles();
-                let removed_tiles_vec = removed_tiles
+                    .downcast_ref::<VecModel<ScoreTileData>>(),
+                removed_tiles
                     .as_any()
-                    .downcast_ref::<VecModel<ScoreTileData>>()
-                    .unwrap();
-                let total = score_tiles_vec.row_count() + removed_tiles_vec.row_count();
-
-                let (real_index, removed_single) = score_tiles_vec
-                    .iter()
-                    .enumerate()
-                    .find(|(_, tile)| tile.index == index)
-                    .unwrap();
-                score_tiles_vec.remove(real_index);
-                removed_tiles_vec.push(removed_single);
-                // 最好排一下序
-                let mut removed_vec_data = removed_tiles_vec.iter().collect::<Vec<_>>();
-                removed_vec_data.sort_by(|a, b| a.index.cmp(&b.index));
-                removed_tiles_vec.set_vec(removed_vec_data);
-
-                // 寻找不在当前列表中的第一个rating
-                // 实际上就是score_tiles和removed_tiles的总数
-                if ratings.len() > total {
-                    let new_info = &ratings[total];
-                    let new_pixmap = generate_single_card_pixmap(total, new_info).unwrap();
-                    let pixel_buffer = SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(
-                        new_pixmap.data(),
-                        new_pixmap.width(),
-                        new_pixmap.height(),
-                    );
-                    let new_data = ScoreTileData {
-                        image: Image::from_rgba8(pixel_buffer),
-                        index: total as i32,
-                    };
-                    score_tiles_vec.push(new_data);
-                }
+                    .downcast_ref::<VecModel<ScoreTileData>>(),
+            ) else {
+                ui.set_text_content(SharedString::from(""));
+                return;
+            };
+            if score_tiles_vec.row_count() == 0 {
+                return;
+            }
 
+            let Some((real_index, removed_single)) = score_tiles_vec
+                .iter()
+                .enumerate()
+                .find(|(_, tile)| tile.index == index)
+            else {
                 ui.set_text_content(SharedString::from(""));
                 ui.set_export_enable(true);
-            })
-            .unwrap();
+                return;
+            };
+            score_tiles_vec.remove(real_index);
+            insert_by_index(removed_tiles_vec, removed_single);
+
+            // 寻找不在当前列表和备选区中的第一个rating，备选区中可能有序号更大的重复成绩
+            let used = score_tiles_vec
+                .iter()
+                .chain(removed_tiles_vec.iter())
+                .map(|tile| tile.index as usize)
+                .collect::<HashSet<_>>();
+            if let Some(next) = (0..ratings.len()).find(|i| !used.contains(i))
+                && let Some(new_pixmap) = render_card(next, &ratings[next])
+            {
+                score_tiles_vec.push(pixmap_tile(next, &new_pixmap));
+            }
+
+            ui.set_text_content(SharedString::from(""));
+            ui.set_export_enable(true);
+            refresh_overall_rating(&ui);
+        });
     }
 }
 
@@ -135,133 +211,542 @@ pub async fn add_tile(
 ) {
     let player_ratings = {
         let r = rating_add.lock().unwrap();
-        r.get(player_name.as_str()).cloned()
+        player_cards(player_name.as_str(), &r)
     };
-    if let Some(_ratings) = player_ratings {
-        ui_handle
-            .upgrade_in_event_loop(move |ui| {
-                let score_tiles = ui.get_score_tiles();
-                let score_tiles_vec = score_tiles
-                    .as_any()
-                    .downcast_ref::<VecModel<ScoreTileData>>()
-                    .unwrap();
+    if let Some(ratings) = player_ratings {
+        let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+            ui.set_text_content(SharedString::from(""));
+            ui.set_export_enable(true);
 
-                let removed_tiles = ui.get_removed_tiles();
-                let removed_tiles_vec = removed_tiles
+            let score_tiles = ui.get_score_tiles();
+            let removed_tiles = ui.get_removed_tiles();
+            let (Some(score_tiles_vec), Some(removed_tiles_vec)) = (
+                score_tiles
+                    .as_any()
+                    .downcast_ref::<VecModel<ScoreTileData>>(),
+                removed_tiles
                     .as_any()
-                    .downcast_ref::<VecModel<ScoreTileData>>()
-                    .unwrap();
-                let (real_index, removed_single) = removed_tiles_vec
+                    .downcast_ref::<VecModel<ScoreTileData>>(),
+            ) else {
+                return;
+            };
+            let Some(info) = ratings.get(index as usize) else {
+                return;
+            };
+            if score_tiles_vec.iter().any(|tile| tile.index == index) {
+                return;
+            }
+
+            // 成绩表已满时挤出Rating最低且未固定的卡片
+            let rating_of = |tile: &ScoreTileData| {
+                ratings
+                    .get(tile.index as usize)
+                    .map_or(0.0, |info| info.rating)
+            };
+            let evicted_row = if score_tiles_vec.row_count() >= table_size() {
+                let Some((row, _)) = score_tiles_vec
                     .iter()
                     .enumerate()
-                    .find(|(_, tile)| tile.index == index)
-                    .unwrap();
+                    .filter(|(_, tile)| !tile.pinned)
+                    .min_by(|(_, a), (_, b)| rating_of(a).total_cmp(&rating_of(b)))
+                else {
+                    notify(Level::Warn, tr!("成绩表中的卡片都已固定，无法加入新的成绩"));
+                    return;
+                };
+                Some(row)
+            } else {
+                None
+            };
 
-                // removed_tiles_vec去除real_index，score_tiles_vec去除最后一个，再push进去 removed_single
-                removed_tiles_vec.remove(real_index);
-                if score_tiles_vec.row_count() == 30 {
-                    score_tiles_vec.remove(score_tiles_vec.row_count() - 1);
-                }
-                score_tiles_vec.push(removed_single);
+            // 备选区中已有的卡片直接移过来，否则从玩家的全部成绩中生成
+            let new_tile = match removed_tiles_vec
+                .iter()
+                .position(|tile| tile.index == index)
+            {
+                Some(row) => removed_tiles_vec.remove(row),
+                None => match render_card(index as usize, info) {
+                    Some(pixmap) => pixmap_tile(index as usize, &pixmap),
+                    None => return,
+                },
+            };
+            if let Some(row) = evicted_row {
+                let evicted = score_tiles_vec.remove(row);
+                insert_by_index(removed_tiles_vec, evicted);
+            }
+            // 插在第一张序号更大的卡片之前，不打乱手动调整过的顺序
+            let row = score_tiles_vec
+                .iter()
+                .position(|tile| tile.index > index)
+                .unwrap_or(score_tiles_vec.row_count());
+            score_tiles_vec.insert(row, new_tile);
+            refresh_overall_rating(&ui);
+        });
+    }
+}
+
+/// 按成绩表中的卡片和设置的计算方式更新界面上的总Rating
+pub fn refresh_overall_rating(ui: &MainWindow) {
+    let player_name = ui.get_current_player_name();
+    let cards = player_cards(player_name.as_str(), &SCORES_DATA.lock().unwrap());
+    let text = match cards {
+        Some(cards) => {
+            let score_tiles = ui.get_score_tiles();
+            let overall = overall_rating(
+                score_tiles
+                    .iter()
+                    .filter_map(|tile| cards.get(tile.index as usize)),
+            );
+            format!("{overall:.02}")
+        }
+        None => String::new(),
+    };
+    ui.set_overall_label(SharedString::from(overall_definition().short_label()));
+    ui.set_overall_rating(SharedString::from(text));
+}
 
-                // 最好排一下序
-                let mut score_vec_data = score_tiles_vec.iter().collect::<Vec<_>>();
-                score_vec_data.sort_by(|a, b| a.index.cmp(&b.index));
-                score_tiles_vec.set_vec(score_vec_data);
+/// 固定或取消固定成绩表中的卡片
+pub fn toggle_pin(index: i32, ui_handle: Weak<MainWindow>) {
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        let score_tiles = ui.get_score_tiles();
+        if let Some(row) = score_tiles.iter().position(|tile| tile.index == index)
+            && let Some(mut tile) = score_tiles.row_data(row)
+        {
+            tile.pinned = !tile.pinned;
+            score_tiles.set_row_data(row, tile);
+        }
+    });
+}
 
-                ui.set_text_content(SharedString::from(""));
-                ui.set_export_enable(true);
-            })
-            .unwrap();
+/// 把卡片移动到成绩表中的指定位置，导出时按这个顺序排列
+pub fn move_tile(index: i32, slot: i32, ui_handle: Weak<MainWindow>) {
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        let score_tiles = ui.get_score_tiles();
+        let Some(score_tiles_vec) = score_tiles
+            .as_any()
+            .downcast_ref::<VecModel<ScoreTileData>>()
+        else {
+            return;
+        };
+        let Some(row) = score_tiles_vec.iter().position(|tile| tile.index == index) else {
+            return;
+        };
+        let slot = (slot.max(0) as usize).min(score_tiles_vec.row_count() - 1);
+        if row != slot {
+            let tile = score_tiles_vec.remove(row);
+            score_tiles_vec.insert(slot, tile);
+        }
+    });
+}
+
+/// 玩家成绩表中的所有成绩，后面接着因为重复被去掉的成绩，与卡片的序号一一对应
+pub fn player_cards(
+    player_name: &str,
+    scores: &HashMap<String, Vec<RatingInfo>>,
+) -> Option<Vec<RatingInfo>> {
+    scores.get(player_name).cloned().map(|mut ratings| {
+        ratings.extend(removed_duplicates(player_name, scores));
+        ratings
+    })
+}
+
+fn pixmap_tile(index: usize, pixmap: &Pixmap) -> ScoreTileData {
+    let pixel_buffer = SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(
+        pixmap.data(),
+        pixmap.width(),
+        pixmap.height(),
+    );
+    ScoreTileData {
+        image: Image::from_rgba8(pixel_buffer),
+        index: index as i32,
+        pinned: false,
     }
 }
 
+/// 卡片生成失败时使用没有图片的占位卡片，保持序号与成绩对应，点击仍可打开详情
+pub(crate) fn card_tile(index: usize, pixmap: Option<&Pixmap>) -> ScoreTileData {
+    match pixmap {
+        Some(pixmap) => pixmap_tile(index, pixmap),
+        None => ScoreTileData {
+            image: Image::default(),
+            index: index as i32,
+            pinned: false,
+        },
+    }
+}
+
+/// 按序号插入备选区
+fn insert_by_index(tiles: &VecModel<ScoreTileData>, mut tile: ScoreTileData) {
+    tile.pinned = false;
+    let row = tiles
+        .iter()
+        .position(|other| other.index > tile.index)
+        .unwrap_or(tiles.row_count());
+    tiles.insert(row, tile);
+}
+
 pub async fn export(
     player_name: SharedString,
     rating_export: Arc<Mutex<HashMap<String, Vec<RatingInfo>>>>,
     ui_handle: Weak<MainWindow>,
 ) {
+    // 卡片的序号包括备选区中的重复成绩
     let player_ratings = {
         let r = rating_export.lock().unwrap();
-        r.get(player_name.as_str()).cloned()
+        player_cards(player_name.as_str(), &r)
     };
     if let Some(ratings) = player_ratings {
         let ratings = ratings.clone();
-        ui_handle
-            .upgrade_in_event_loop(move |ui: MainWindow| {
-                let score_tiles = ui.get_score_tiles();
-                let score_tiles_vec = score_tiles
-                    .as_any()
-                    .downcast_ref::<VecModel<ScoreTileData>>()
-                    .unwrap();
-                let exported_indexes = score_tiles_vec
+        let _ = ui_handle.upgrade_in_event_loop(move |ui: MainWindow| {
+            let score_tiles = ui.get_score_tiles();
+            let Some(score_tiles_vec) = score_tiles
+                .as_any()
+                .downcast_ref::<VecModel<ScoreTileData>>()
+            else {
+                ui.set_export_enable(true);
+                return;
+            };
+            if export_format() == ExportFormat::Pdf {
+                // PDF重新生成矢量的卡片，在后台进行
+                let cards = score_tiles_vec
                     .iter()
-                    .map(|tile| tile.index as usize)
+                    .filter_map(|tile| {
+                        let index = tile.index as usize;
+                        ratings.get(index).map(|info| (index, info.clone()))
+                    })
                     .collect::<Vec<_>>();
-                let average_rating = ratings
-                    .par_iter()
-                    .enumerate()
-                    .filter(|(index, _)| exported_indexes.contains(index))
-                    .map(|(_, info)| info.rating)
-                    .sum::<f64>()
-                    / exported_indexes.len() as f64;
-
-                let info_vec = score_tiles_vec
+                let ui_handle = ui.as_weak();
+                tokio::spawn(async move {
+                    let result = spawn_blocking(move || {
+                        let average_rating = overall_rating(cards.iter().map(|(_, info)| info));
+                        export_pdf(
+                            player_name.as_str(),
+                            None,
+                            &cards,
+                            average_rating,
+                            cards.len(),
+                        )
+                    })
+                    .await
+                    .map_err(AppError::from)
+                    .and_then(|result| result);
+                    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+                        ui.set_export_enable(true);
+                        report_export(&ui, result);
+                    });
+                });
+                return;
+            }
+            ui.set_export_enable(true);
+            // 总Rating只计算成绩表中实际导出的卡片
+            let average_rating = overall_rating(
+                score_tiles_vec
                     .iter()
-                    .enumerate()
-                    .map(|(index, tile)| ScoreTileBase64 {
+                    .filter_map(|tile| ratings.get(tile.index as usize)),
+            );
+
+            let info_vec = score_tiles_vec
+                .iter()
+                .enumerate()
+                .map(|(index, tile)| {
+                    Ok(ScoreTileBase64 {
                         index: index as i32,
-                        base64_string: {
-                            let raw_image = tile.image.to_rgba8().unwrap().as_bytes().to_vec();
-                            let pixmap =
-                                Pixmap::from_vec(raw_image, IntSize::from_wh(1200, 350).unwrap())
-                                    .unwrap();
-                            let png_data = pixmap.encode_png().unwrap();
-                            BASE64_STANDARD.encode(&png_data)
-                        },
+                        base64_string: tile_to_base64(&tile.image)?,
                     })
-                    .collect::<Vec<_>>();
-
-                match export_info(player_name.as_str(), info_vec, average_rating) {
-                    Ok(path) => {
-                        ui.set_text_content(slint::format!(
-                            "导出完成! 导出路径: {}",
-                            path.display()
-                        ));
-                        open::that(path).unwrap();
-                    }
-                    Err(e) => {
-                        ui.set_text_content(slint::format!("导出失败: {}", e));
-                    }
-                }
+                })
+                .collect::<AppResult<Vec<_>>>();
 
-                ui.set_export_enable(true);
-            })
-            .unwrap();
+            let manifest = ExportManifest::new(
+                player_name.as_str(),
+                score_tiles_vec.iter().filter_map(|tile| {
+                    let index = tile.index as usize;
+                    ratings.get(index).map(|info| (index, info))
+                }),
+            );
+            let result = info_vec.and_then(|info_vec| {
+                export_info(player_name.as_str(), info_vec, average_rating, &manifest)
+            });
+            report_export(&ui, result);
+        });
     } else {
-        ui_handle.unwrap().set_export_enable(true);
+        let _ = ui_handle.upgrade_in_event_loop(|ui| ui.set_export_enable(true));
     }
 }
 
-pub async fn copy_image(width: usize, height: usize, bytes: Vec<u8>) {
+/// 在状态栏显示导出结果，成功时打开导出的文件
+fn report_export(ui: &MainWindow, result: AppResult<PathBuf>) {
+    match result {
+        Ok(path) => {
+            ui.set_text_content(SharedString::from(tr!(
+                "导出完成! 导出路径: {}",
+                path.display()
+            )));
+            if let Err(e) = open::that(&path) {
+                notify(Level::Warn, tr!("无法打开 {}: {}", path.display(), e));
+            }
+        }
+        Err(e) => {
+            ui.set_text_content(SharedString::from(tr!("导出失败")));
+            notify(Level::Error, tr!("导出失败: {}", e));
+        }
+    }
+}
+
+/// 将界面上的卡片重新编码为PNG的base64
+fn tile_to_base64(image: &Image) -> AppResult<String> {
+    let raw_image = image
+        .to_rgba8()
+        .ok_or_else(|| AppError::Render(tr!("无法读取卡片图像")))?
+        .as_bytes()
+        .to_vec();
+    let size = IntSize::from_wh(1200, 350).ok_or_else(|| AppError::Render(tr!("卡片尺寸错误")))?;
+    let pixmap =
+        Pixmap::from_vec(raw_image, size).ok_or_else(|| AppError::Render(tr!("卡片尺寸不匹配")))?;
+    let png_data = pixmap
+        .encode_png()
+        .map_err(|e| AppError::Render(e.to_string()))?;
+    Ok(BASE64_STANDARD.encode(&png_data))
+}
+
+/// 载入实时模式的记录文件：卡片追加到实时模式列表，成绩合并进对应玩家的成绩表
+pub async fn load_session(path: PathBuf, ui_handle: Weak<MainWindow>) {
+    let loaded = spawn_blocking(move || load_journal(&path))
+        .await
+        .map_err(AppError::from)
+        .and_then(|loaded| loaded.map_err(AppError::from));
+    let (ratings, skipped) = match loaded {
+        Ok(v) => v,
+        Err(e) => {
+            notify(Level::Error, tr!("载入记录失败: {}", e));
+            return;
+        }
+    };
+
+    // IMPORTED_RATINGS和SCORES_DATA不同时持有，避免与后台刷新死锁
+    IMPORTED_RATINGS.lock().unwrap().extend(
+        ratings
+            .iter()
+            .filter(|info| !info.score_info.failed)
+            .cloned(),
+    );
+    let changed_players = merge_ratings(&mut SCORES_DATA.lock().unwrap(), &ratings);
+    sync_player_models(changed_players, ui_handle.clone());
+    // 卡片的序号为成绩在REALTIME_DATA中的位置
+    let base = {
+        let mut realtime = REALTIME_DATA.lock().unwrap();
+        let base = realtime.len();
+        realtime.extend(ratings.iter().cloned());
+        base
+    };
+    update_session_summary(ui_handle.clone());
+
+    let count = ratings.len();
+    let pixmaps = match spawn_blocking(move || {
+        ratings
+            .par_iter()
+            .enumerate()
+            .map(|(i, r)| (base + i, render_card(base + i, r)))
+            .collect::<Vec<_>>()
+    })
+    .await
+    {
+        Ok(pixmaps) => pixmaps,
+        Err(e) => {
+            notify(Level::Error, tr!("卡片生成失败: {}", e));
+            return;
+        }
+    };
+
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        let realtime_tiles = ui.get_realtime_tiles();
+        let mut tiles = realtime_tiles.iter().collect::<Vec<_>>();
+        // 与实时模式的卡片一样，较新的成绩在前
+        tiles.extend(
+            pixmaps
+                .iter()
+                .rev()
+                .map(|(i, pixmap)| card_tile(*i, pixmap.as_ref())),
+        );
+        ui.set_realtime_tiles(ModelRc::new(VecModel::from(tiles)));
+        ui.set_test_content(SharedString::from(tr!("已载入{}个成绩", count)));
+        ui.window().request_redraw();
+    });
+    if skipped > 0 {
+        notify(
+            Level::Warn,
+            tr!(
+                "记录中有{}个成绩无法恢复（格式错误或谱面文件已不存在）",
+                skipped
+            ),
+        );
+    }
+}
+
+/// 根据实时模式当前的成绩更新界面上的总结文字
+pub fn update_session_summary(ui_handle: Weak<MainWindow>) {
+    let summary = summarize_session(&REALTIME_DATA.lock().unwrap(), &SCORES_DATA.lock().unwrap());
+    let text = match summary {
+        Some(summary) => tr!(
+            "{}: {}次游玩, 平均Rating {}, 最佳 {}, {} {} → {}",
+            summary.player,
+            summary.play_count,
+            format!("{:.02}", summary.average_rating),
+            format!("{:.02}", summary.best_play.rating),
+            summary.overall.short_label(),
+            format!("{:.02}", summary.b30_before),
+            format!("{:.02}", summary.b30_after)
+        ),
+        None => String::new(),
+    };
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        ui.set_session_summary(SharedString::from(text));
+    });
+}
+
+/// 将实时模式的新成绩与玩家当前的B30比较，进入B30的成绩可以通过"加入B30"按钮合并进成绩表
+pub fn update_b30_delta(new_score: RatingInfo, ui_handle: Weak<MainWindow>) {
+    let current = SCORES_DATA
+        .lock()
+        .unwrap()
+        .get(&player_list_name(new_score.score_info.player.clone()))
+        .cloned()
+        .unwrap_or_default();
+    let delta = compute_b30_delta(&current, &new_score);
+
+    let title = |info: &RatingInfo| {
+        let beatmap_info = &info.map_info.info;
+        format!(
+            "{} [{}]",
+            beatmap_info
+                .title_unicode
+                .as_ref()
+                .unwrap_or(&beatmap_info.title),
+            beatmap_info.version
+        )
+    };
+    let average_text = tr!(
+        "{} {} → {} ({})",
+        overall_definition().short_label(),
+        format!("{:.02}", delta.average_before),
+        format!("{:.02}", delta.average_after),
+        format!("{:+.02}", delta.average_after - delta.average_before)
+    );
+    let text = match (delta.new_rank, &delta.replaced) {
+        _ if !counts_for_b30(&new_score) => tr!(
+            "{} ({}) 的谱面状态为{}，不计入B{}",
+            title(&new_score),
+            format!("{:.02}", new_score.rating),
+            new_score.map_info.status.label(),
+            delta.slots
+        ),
+        (Some(rank), Some(replaced)) if delta.enters_b30() => tr!(
+            "{} ({}) 进入B{} #{}, 替换 {} ({}), {}",
+            title(&new_score),
+            format!("{:.02}", new_score.rating),
+            delta.slots,
+            rank,
+            title(replaced),
+            format!("{:.02}", replaced.rating),
+            average_text
+        ),
+        (Some(rank), None) if delta.enters_b30() => tr!(
+            "{} ({}) 进入B{} #{}, {}",
+            title(&new_score),
+            format!("{:.02}", new_score.rating),
+            delta.slots,
+            rank,
+            average_text
+        ),
+        (Some(rank), _) => tr!(
+            "{} ({}) 未进入B{}, 排名 #{}",
+            title(&new_score),
+            format!("{:.02}", new_score.rating),
+            delta.slots,
+            rank
+        ),
+        (None, _) => tr!(
+            "{} ({}) 未超过该谱面已有的成绩",
+            title(&new_score),
+            format!("{:.02}", new_score.rating)
+        ),
+    };
+
+    publish(OverlayEvent::score(&new_score, &delta));
+
+    let enters_b30 = delta.enters_b30();
+    *PENDING_SCORE.lock().unwrap() = enters_b30.then_some(new_score);
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        ui.set_b30_delta(SharedString::from(text));
+        ui.set_pending_score(enters_b30);
+    });
+}
+
+/// 将等待中的实时成绩合并进成绩表，并刷新主界面的B30列表
+pub fn accept_pending_score(ui_handle: Weak<MainWindow>) {
+    let Some(score) = PENDING_SCORE.lock().unwrap().take() else {
+        return;
+    };
+    IMPORTED_RATINGS.lock().unwrap().push(score.clone());
+    let changed_players = merge_ratings(&mut SCORES_DATA.lock().unwrap(), &[score]);
+    sync_player_models(changed_players, ui_handle.clone());
+    update_session_summary(ui_handle.clone());
+    let _ = ui_handle.upgrade_in_event_loop(|ui| {
+        ui.set_pending_score(false);
+        ui.set_b30_delta(SharedString::from(tr!("已加入B30")));
+    });
+}
+
+pub async fn export_session(ui_handle: Weak<MainWindow>) {
+    let summary = summarize_session(&REALTIME_DATA.lock().unwrap(), &SCORES_DATA.lock().unwrap());
+    let Some(summary) = summary else {
+        let _ = ui_handle.upgrade_in_event_loop(|ui| {
+            ui.set_test_content(SharedString::from(tr!("实时模式中还没有成绩")));
+        });
+        return;
+    };
+
+    let result = spawn_blocking(move || export_session_summary(&summary))
+        .await
+        .map_err(AppError::from)
+        .and_then(|result| result);
+    match result {
+        Ok(path) => {
+            if let Err(e) = open::that(&path) {
+                notify(Level::Warn, tr!("无法打开 {}: {}", path.display(), e));
+            }
+            let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+                ui.set_test_content(SharedString::from(tr!(
+                    "导出完成! 导出路径: {}",
+                    path.display()
+                )));
+            });
+        }
+        Err(e) => notify(Level::Error, tr!("导出失败: {}", e)),
+    }
+}
+
+pub async fn copy_image(width: usize, height: usize, bytes: Vec<u8>) -> AppResult<()> {
     let image_data = arboard::ImageData {
         width,
         height,
         bytes: bytes.into(),
     };
 
-    let mut clipboard = Clipboard::new().unwrap();
-    clipboard.set_image(image_data).unwrap();
+    let mut clipboard = Clipboard::new()?;
+    clipboard.set_image(image_data)?;
+    Ok(())
 }
 
 pub fn select_osu_folder() -> Option<String> {
     let path = DialogBuilder::file()
-        .set_location("~/Desktop")
-        .set_title("选择osu!文件夹")
+        .set_location(&default_dialog_location())
+        .set_title(tr!("选择osu!文件夹"))
         .open_single_dir()
         .show()
-        .unwrap();
-    println!("{path:?}");
+        .unwrap_or_else(|e| {
+            notify(Level::Error, AppError::from(e).to_string());
+            None
+        });
+    log::info!("Selected osu! folder: {path:?}");
     if let Some(path) = path {
         let scores_db_path = path.join("scores.db");
         let osu_db_path = path.join("osu!.db");
@@ -272,9 +757,40 @@ pub fn select_osu_folder() -> Option<String> {
     DialogBuilder::message()
         .set_level(MessageLevel::Error)
         .set_title("Cannot find db")
-        .set_text("该路径下没有osu!.db和scores.db文件！")
+        .set_text(tr!("该路径下没有osu!.db和scores.db文件！"))
         .alert()
         .show()
-        .unwrap();
+        .unwrap_or_else(|e| log::error!("{}", AppError::from(e)));
     None
 }
+
+pub fn select_session_file() -> Option<PathBuf> {
+    let location = session_dir()
+        .ok()
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(default_dialog_location);
+    DialogBuilder::file()
+        .set_location(&location)
+        .set_title(tr!("选择实时模式记录"))
+        .add_filter("Session", ["jsonl"])
+        .open_single_file()
+        .show()
+        .unwrap_or_else(|e| {
+            notify(Level::Error, AppError::from(e).to_string());
+            None
+        })
+}
+
+/// 选择谱面状态的镜像文件
+pub fn select_status_mirror_file() -> Option<PathBuf> {
+    DialogBuilder::file()
+        .set_location(&default_dialog_location())
+        .set_title(tr!("选择谱面状态镜像文件"))
+        .add_filter("CSV", ["csv"])
+        .open_single_file()
+        .show()
+        .unwrap_or_else(|e| {
+            notify(Level::Error, AppError::from(e).to_string());
+            None
+        })
+}
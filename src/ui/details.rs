@@ -0,0 +1,221 @@
+use crate::db::{MapDetails, RatingInfo, SrVariant, load_map_details, replay_path};
+use crate::graphx::{format_bpm_str, format_length_str};
+use crate::tr;
+use crate::ui::callbacks::player_cards;
+use crate::ui::notify::notify;
+use crate::{DetailField, DetailWindow, REALTIME_DATA, SCORES_DATA};
+use arboard::Clipboard;
+use chrono::Local;
+use log::Level;
+use slint::{ComponentHandle, ModelRc, SharedString, StandardListViewItem, VecModel};
+use std::path::Path;
+use tokio::task::spawn_blocking;
+
+/// 成绩表中序号对应的成绩，序号包括备选区中的重复成绩
+pub fn player_card(player_name: &str, index: usize) -> Option<RatingInfo> {
+    let scores = SCORES_DATA.lock().unwrap();
+    player_cards(player_name, &scores)?.into_iter().nth(index)
+}
+
+/// 实时模式中序号对应的成绩
+pub fn realtime_card(index: usize) -> Option<RatingInfo> {
+    REALTIME_DATA.lock().unwrap().get(index).cloned()
+}
+
+/// 打开成绩详情窗口。OD、HP和各mod下的星级需要重新读取谱面，读取完成后再填入
+pub fn show_score_details(info: RatingInfo, osu_dir: String) {
+    let detail_window = match DetailWindow::new() {
+        Ok(detail_window) => detail_window,
+        Err(e) => {
+            notify(Level::Error, tr!("无法打开成绩详情: {}", e));
+            return;
+        }
+    };
+    let beatmap_info = &info.map_info.info;
+    let replay = replay_path(Path::new(&osu_dir), &info);
+    detail_window.set_heading(SharedString::from(format!(
+        "{} - {} [{}]",
+        beatmap_info.artist, beatmap_info.title, beatmap_info.version
+    )));
+    detail_window.set_fields(detail_fields(&info, None));
+    detail_window.set_plays(play_rows(&info));
+    detail_window.set_replay_available(replay.exists());
+
+    let folder = info.map_info.path.parent().map(Path::to_path_buf);
+    detail_window.on_open_folder(move || {
+        let Some(folder) = &folder else {
+            return;
+        };
+        if let Err(e) = open::that(folder) {
+            notify(Level::Warn, tr!("无法打开文件夹: {}", e));
+        }
+    });
+    detail_window.on_open_replay(move || open_replay(&replay));
+    let hash = info.map_info.hash.clone();
+    detail_window.on_copy_hash(move || {
+        match Clipboard::new().and_then(|mut clipboard| clipboard.set_text(hash.clone())) {
+            Ok(()) => notify(Level::Info, tr!("Hash已复制到剪贴板")),
+            Err(e) => notify(Level::Error, tr!("复制失败: {}", e)),
+        }
+    });
+
+    if let Err(e) = detail_window.show() {
+        notify(Level::Error, tr!("无法打开成绩详情: {}", e));
+        return;
+    }
+
+    let window_handle = detail_window.as_weak();
+    tokio::spawn(async move {
+        let path = info.map_info.path.clone();
+        let details = match spawn_blocking(move || load_map_details(&path)).await {
+            Ok(Ok(details)) => details,
+            Ok(Err(e)) => {
+                log::warn!("Failed to read {}: {e}", info.map_info.path.display());
+                return;
+            }
+            Err(e) => {
+                log::warn!("Failed to compute map details: {e}");
+                return;
+            }
+        };
+        let _ = window_handle.upgrade_in_event_loop(move |window| {
+            window.set_fields(detail_fields(&info, Some(&details)));
+        });
+    });
+}
+
+fn open_replay(replay: &Path) {
+    if !replay.exists() {
+        notify(Level::Warn, tr!("找不到回放文件 {}", replay.display()));
+        return;
+    }
+    if let Err(e) = open::that(replay) {
+        notify(Level::Warn, tr!("无法打开回放: {}", e));
+    }
+}
+
+/// 详情窗口中的字段，谱面详情还没有读取完成时显示占位
+fn detail_fields(info: &RatingInfo, details: Option<&MapDetails>) -> ModelRc<DetailField> {
+    let beatmap_info = &info.map_info.info;
+    let score_info = &info.score_info;
+    let pending = || tr!("读取中...");
+    let total_count = beatmap_info.note_count + beatmap_info.ln_count;
+    let mut fields = vec![
+        (tr!("标题"), beatmap_info.title.clone()),
+        (tr!("艺术家"), beatmap_info.artist.clone()),
+        (tr!("谱师"), beatmap_info.creator.clone()),
+        (tr!("难度名"), beatmap_info.version.clone()),
+        (tr!("谱面ID"), beatmap_info.beatmap_id.to_string()),
+        (tr!("谱面集ID"), beatmap_info.beatmap_set_id.to_string()),
+        (tr!("状态"), info.map_info.status.label()),
+        (tr!("Hash"), info.map_info.hash.clone()),
+        (tr!("文件"), info.map_info.path.display().to_string()),
+        (tr!("键数"), format!("{}K", beatmap_info.column_count)),
+        (
+            tr!("物量"),
+            tr!(
+                "{}（米 {} / 面 {}）",
+                total_count,
+                beatmap_info.note_count,
+                beatmap_info.ln_count
+            ),
+        ),
+        (
+            tr!("BPM"),
+            format_bpm_str(beatmap_info.min_bpm, beatmap_info.max_bpm),
+        ),
+        (tr!("长度"), format_length_str(beatmap_info.length)),
+        (
+            tr!("OD"),
+            details.map_or_else(pending, |d| format!("{:.1}", d.od)),
+        ),
+        (
+            tr!("HP"),
+            details.map_or_else(pending, |d| format!("{:.1}", d.hp)),
+        ),
+    ];
+    for variant in SrVariant::ALL {
+        let sr = details
+            .and_then(|d| d.srs.iter().find(|(v, _)| *v == variant))
+            .map_or_else(pending, |(_, sr)| format!("{sr:.2}"));
+        fields.push((tr!("{}星级", variant.label()), sr));
+    }
+    let [marv, perf, great, good, bad, miss] = score_info.judgement_num;
+    fields.extend([
+        (tr!("玩家"), score_info.player.clone()),
+        (
+            tr!("时间"),
+            score_info
+                .timestamp
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+        ),
+        (tr!("Mods"), non_empty(score_info.mods_text())),
+        (
+            tr!("判定"),
+            format!("{marv} / {perf} / {great} / {good} / {bad} / {miss}"),
+        ),
+        (tr!("Acc"), format!("{:.2}%", score_info.accuracy)),
+        (
+            tr!("Rating Acc"),
+            format!("{:.2}%", score_info.accuracy_rating),
+        ),
+        (tr!("定数"), format!("{:.2}", info.diff_const)),
+        (tr!("Rating"), format!("{:.2}", info.rating)),
+    ]);
+    let fields = fields
+        .into_iter()
+        .map(|(label, value)| DetailField {
+            label: SharedString::from(label),
+            value: SharedString::from(value),
+        })
+        .collect::<Vec<_>>();
+    ModelRc::new(VecModel::from(fields))
+}
+
+/// 该玩家在同一张谱面上的所有成绩，按Rating从高到低排序
+fn play_rows(info: &RatingInfo) -> ModelRc<ModelRc<StandardListViewItem>> {
+    // [Recent 30]中保存着所有成绩
+    let mut plays = SCORES_DATA
+        .lock()
+        .unwrap()
+        .get("[Recent 30]")
+        .into_iter()
+        .flatten()
+        .filter(|play| {
+            play.map_info.hash == info.map_info.hash
+                && play.score_info.player == info.score_info.player
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    plays.sort_unstable_by(|a, b| b.rating.total_cmp(&a.rating));
+    let rows = plays
+        .iter()
+        .map(|play| {
+            let cells = [
+                play.score_info
+                    .timestamp
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+                non_empty(play.score_info.mods_text()),
+                format!("{:.2}%", play.score_info.accuracy),
+                format!("{:.2}%", play.score_info.accuracy_rating),
+                format!("{:.2}", play.diff_const),
+                format!("{:.2}", play.rating),
+            ]
+            .map(|cell| StandardListViewItem::from(SharedString::from(cell)));
+            ModelRc::new(VecModel::from(cells.to_vec()))
+        })
+        .collect::<Vec<_>>();
+    ModelRc::new(VecModel::from(rows))
+}
+
+fn non_empty(text: String) -> String {
+    if text.is_empty() {
+        "-".to_string()
+    } else {
+        text
+    }
+}
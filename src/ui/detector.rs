@@ -0,0 +1,439 @@
+use osu_db::{Mod, ModSet};
+use rosu_memory_lib::common::{GameMode, GameState};
+
+use crate::ui::memory::{BeatmapData, GameMemorySource, ResultScreenData};
+
+/// 实时模式检测到的事件
+#[derive(Debug, Clone)]
+pub enum RealtimeEvent {
+    StateChanged(GameState),
+    /// 开始游玩，附带当前谱面用于实时估算
+    PlayStarted {
+        mods: ModSet,
+        beatmap: Option<BeatmapData>,
+    },
+    /// 游玩中的判定数量有变化
+    PlayProgress {
+        hits: [u32; 6],
+    },
+    /// 游玩中途重新开始（判定数量清零）
+    PlayRestarted,
+    /// 血量归零。失败的成绩不会写入scores.db，附带失败时的判定数量以便记录
+    PlayFailed {
+        hits: [u32; 6],
+        mods: ModSet,
+        beatmap: Option<BeatmapData>,
+    },
+    /// 没有到达结算界面就退出了游玩，包括多人游戏的结算
+    PlayQuit,
+    ReplayViewed,
+    ScoreSubmitted {
+        result: ResultScreenData,
+        beatmap: BeatmapData,
+        mods: ModSet,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Phase {
+    /// 菜单、选歌等不在游玩中的状态
+    Idle,
+    Playing {
+        mods: ModSet,
+        failed: bool,
+        hits_total: u32,
+    },
+    Replay,
+    /// 游玩结束进入结算界面，等待读取到成绩
+    AwaitingResult {
+        mods: ModSet,
+    },
+    /// 已经读取过或者不需要读取的结算界面
+    ViewingResult,
+}
+
+/// 实时模式的新成绩检测状态机。
+/// 只有从一次本地游玩直接进入单人结算界面时才读取成绩。进入Playing时读取内存中的回放标记，
+/// 因此选歌界面排行榜中的回放和结算界面的重试都能正确区分
+pub struct ScoreDetector {
+    prev_state: GameState,
+    phase: Phase,
+}
+
+impl ScoreDetector {
+    pub fn new() -> Self {
+        ScoreDetector {
+            prev_state: GameState::Unknown,
+            phase: Phase::Idle,
+        }
+    }
+
+    pub fn poll(&mut self, source: &mut impl GameMemorySource) -> Vec<RealtimeEvent> {
+        let mut events = Vec::new();
+        let gamestate = source.game_state();
+        if gamestate != self.prev_state {
+            events.push(RealtimeEvent::StateChanged(gamestate));
+            self.prev_state = gamestate;
+        }
+
+        self.phase = match (self.phase, gamestate) {
+            (
+                Phase::Idle | Phase::ViewingResult | Phase::AwaitingResult { .. },
+                GameState::Playing,
+            ) => self.start_playing(source, &mut events),
+            (
+                Phase::Playing {
+                    mods,
+                    failed,
+                    hits_total,
+                },
+                GameState::Playing,
+            ) => self.update_playing(source, mods, failed, hits_total, &mut events),
+            (Phase::Playing { failed, .. }, GameState::ResultScreen) if failed => {
+                Phase::ViewingResult
+            }
+            (Phase::Playing { mods, .. }, GameState::ResultScreen)
+            | (Phase::AwaitingResult { mods }, GameState::ResultScreen) => {
+                self.read_result(source, mods, &mut events)
+            }
+            (Phase::Replay, GameState::Playing) => Phase::Replay,
+            (Phase::Replay | Phase::ViewingResult, GameState::ResultScreen) => Phase::ViewingResult,
+            (Phase::Playing { failed, .. }, _) => {
+                if !failed {
+                    events.push(RealtimeEvent::PlayQuit);
+                }
+                Phase::Idle
+            }
+            (Phase::Idle, GameState::ResultScreen) => Phase::ViewingResult,
+            (_, _) => Phase::Idle,
+        };
+        events
+    }
+
+    /// 进入Playing：回放不计入成绩，否则开始一次新的游玩。
+    /// 读取不到回放标记时按游玩处理，结算界面的重试比观看回放更常见
+    fn start_playing(
+        &mut self,
+        source: &mut impl GameMemorySource,
+        events: &mut Vec<RealtimeEvent>,
+    ) -> Phase {
+        if source.is_replay().unwrap_or(false) {
+            events.push(RealtimeEvent::ReplayViewed);
+            return Phase::Replay;
+        }
+        let mods = ModSet::from_bits(source.gameplay_mods().unwrap_or(0));
+        let beatmap = source.beatmap();
+        events.push(RealtimeEvent::PlayStarted { mods, beatmap });
+        Phase::Playing {
+            mods,
+            failed: false,
+            hits_total: 0,
+        }
+    }
+
+    fn update_playing(
+        &mut self,
+        source: &mut impl GameMemorySource,
+        mods: ModSet,
+        failed: bool,
+        hits_total: u32,
+        events: &mut Vec<RealtimeEvent>,
+    ) -> Phase {
+        let Some(hits) = source.gameplay_hits() else {
+            return Phase::Playing {
+                mods,
+                failed,
+                hits_total,
+            };
+        };
+        let new_total = hits.iter().sum::<u32>();
+        if new_total < hits_total {
+            // 快速重试：判定清零，重新读取mod
+            let mods = ModSet::from_bits(source.gameplay_mods().unwrap_or(mods.bits()));
+            let beatmap = source.beatmap();
+            events.push(RealtimeEvent::PlayRestarted);
+            events.push(RealtimeEvent::PlayStarted { mods, beatmap });
+            return Phase::Playing {
+                mods,
+                failed: false,
+                hits_total: new_total,
+            };
+        }
+
+        if new_total > hits_total {
+            events.push(RealtimeEvent::PlayProgress { hits });
+        }
+
+        let hp = source.gameplay_hp().unwrap_or(1.0);
+        let failed = if !failed && new_total > 0 && hp <= 0.0 && !mods.contains(Mod::NoFail) {
+            events.push(RealtimeEvent::PlayFailed {
+                hits,
+                mods,
+                beatmap: source.beatmap(),
+            });
+            true
+        } else {
+            failed
+        };
+        Phase::Playing {
+            mods,
+            failed,
+            hits_total: new_total,
+        }
+    }
+
+    fn read_result(
+        &mut self,
+        source: &mut impl GameMemorySource,
+        mods: ModSet,
+        events: &mut Vec<RealtimeEvent>,
+    ) -> Phase {
+        log::debug!("Reading result screen");
+        let Some(result) = source.result_screen() else {
+            return Phase::AwaitingResult { mods };
+        };
+        match result.mode {
+            GameMode::Mania => {}
+            GameMode::Unknown => {
+                log::debug!("Read fails, retrying...");
+                return Phase::AwaitingResult { mods };
+            }
+            mode => {
+                log::info!("Unsupported game mode: {mode:?}");
+                return Phase::ViewingResult;
+            }
+        }
+
+        log::debug!("Reading beatmap path");
+        let Some(beatmap) = source.beatmap() else {
+            log::debug!("Read fails, retrying...");
+            return Phase::AwaitingResult { mods };
+        };
+        events.push(RealtimeEvent::ScoreSubmitted {
+            result,
+            beatmap,
+            mods,
+        });
+        Phase::ViewingResult
+    }
+}
+
+impl Default for ScoreDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::memory::{ScriptedFrame, ScriptedMemorySource};
+    use std::path::PathBuf;
+
+    fn beatmap() -> BeatmapData {
+        BeatmapData {
+            path: PathBuf::from("Songs/test/test.osu"),
+            md5: "0123456789abcdef0123456789abcdef".into(),
+        }
+    }
+
+    fn result(judgement_num: [u32; 6]) -> ResultScreenData {
+        ResultScreenData {
+            mode: GameMode::Mania,
+            username: "player".into(),
+            judgement_num,
+            accuracy: 98.5,
+        }
+    }
+
+    fn menu() -> ScriptedFrame {
+        ScriptedFrame::new(GameState::SongSelect)
+    }
+
+    fn playing(hits: [u32; 6], hp: f64) -> ScriptedFrame {
+        ScriptedFrame::new(GameState::Playing)
+            .with_mods(0)
+            .with_hits(hits, hp)
+            .with_beatmap(beatmap())
+    }
+
+    fn result_screen(judgement_num: [u32; 6]) -> ScriptedFrame {
+        ScriptedFrame::new(GameState::ResultScreen).with_result(result(judgement_num), beatmap())
+    }
+
+    /// 依次轮询所有帧，去掉状态变化和判定进度，只保留游玩相关的事件
+    fn run(frames: Vec<ScriptedFrame>) -> Vec<RealtimeEvent> {
+        let mut source = ScriptedMemorySource::new(frames);
+        let mut detector = ScoreDetector::new();
+        let mut events = Vec::new();
+        while !source.is_finished() {
+            events.extend(detector.poll(&mut source).into_iter().filter(|event| {
+                !matches!(
+                    event,
+                    RealtimeEvent::StateChanged(_) | RealtimeEvent::PlayProgress { .. }
+                )
+            }));
+        }
+        events
+    }
+
+    fn names(events: &[RealtimeEvent]) -> Vec<&'static str> {
+        events
+            .iter()
+            .map(|event| match event {
+                RealtimeEvent::StateChanged(_) => "state",
+                RealtimeEvent::PlayStarted { .. } => "started",
+                RealtimeEvent::PlayProgress { .. } => "progress",
+                RealtimeEvent::PlayRestarted => "restarted",
+                RealtimeEvent::PlayFailed { .. } => "failed",
+                RealtimeEvent::PlayQuit => "quit",
+                RealtimeEvent::ReplayViewed => "replay",
+                RealtimeEvent::ScoreSubmitted { .. } => "submitted",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn play_to_result_submits_score() {
+        let judgements = [500, 200, 20, 5, 1, 2];
+        let events = run(vec![
+            menu(),
+            playing([0; 6], 1.0),
+            playing([100, 50, 5, 0, 0, 1], 0.8),
+            result_screen(judgements),
+            result_screen(judgements),
+            menu(),
+        ]);
+        assert_eq!(names(&events), ["started", "submitted"]);
+        let RealtimeEvent::ScoreSubmitted { result, .. } = &events[1] else {
+            unreachable!();
+        };
+        assert_eq!(result.judgement_num, judgements);
+    }
+
+    #[test]
+    fn result_screen_is_read_again_until_available() {
+        let events = run(vec![
+            menu(),
+            playing([10, 0, 0, 0, 0, 0], 1.0),
+            ScriptedFrame::new(GameState::ResultScreen),
+            result_screen([10, 0, 0, 0, 0, 0]),
+            menu(),
+        ]);
+        assert_eq!(names(&events), ["started", "submitted"]);
+    }
+
+    #[test]
+    fn failed_play_is_not_submitted() {
+        let events = run(vec![
+            menu(),
+            playing([0; 6], 1.0),
+            playing([20, 10, 5, 5, 5, 30], 0.0),
+            playing([20, 10, 5, 5, 5, 30], 0.0),
+            menu(),
+        ]);
+        assert_eq!(names(&events), ["started", "failed"]);
+        let RealtimeEvent::PlayFailed { hits, beatmap, .. } = &events[1] else {
+            unreachable!();
+        };
+        assert_eq!(*hits, [20, 10, 5, 5, 5, 30]);
+        assert!(beatmap.is_some());
+    }
+
+    #[test]
+    fn no_fail_play_does_not_fail() {
+        let events = run(vec![
+            menu(),
+            // NoFail
+            ScriptedFrame::new(GameState::Playing)
+                .with_mods(1)
+                .with_beatmap(beatmap()),
+            ScriptedFrame::new(GameState::Playing).with_hits([20, 10, 5, 5, 5, 30], 0.0),
+            menu(),
+        ]);
+        assert_eq!(names(&events), ["started", "quit"]);
+    }
+
+    #[test]
+    fn quick_retry_restarts_play() {
+        let events = run(vec![
+            menu(),
+            playing([0; 6], 1.0),
+            playing([50, 20, 0, 0, 0, 3], 0.9),
+            playing([0; 6], 1.0),
+            playing([30, 10, 0, 0, 0, 0], 1.0),
+            result_screen([300, 100, 0, 0, 0, 0]),
+            menu(),
+        ]);
+        assert_eq!(
+            names(&events),
+            ["started", "restarted", "started", "submitted"]
+        );
+    }
+
+    #[test]
+    fn quitting_a_play_is_reported() {
+        let events = run(vec![
+            menu(),
+            playing([0; 6], 1.0),
+            playing([50, 20, 0, 0, 0, 3], 0.9),
+            menu(),
+        ]);
+        assert_eq!(names(&events), ["started", "quit"]);
+    }
+
+    #[test]
+    fn replay_is_not_submitted() {
+        let events = run(vec![
+            menu(),
+            playing([0; 6], 1.0).with_replay(true),
+            playing([50, 20, 0, 0, 0, 3], 0.9).with_replay(true),
+            result_screen([500, 200, 20, 5, 1, 2]),
+            menu(),
+        ]);
+        assert_eq!(names(&events), ["replay"]);
+    }
+
+    #[test]
+    fn replay_from_song_select_is_not_submitted() {
+        let events = run(vec![
+            menu(),
+            playing([0; 6], 1.0).with_replay(true),
+            result_screen([500, 200, 20, 5, 1, 2]),
+            menu(),
+            playing([0; 6], 1.0).with_replay(false),
+            result_screen([400, 100, 20, 5, 1, 2]),
+        ]);
+        assert_eq!(names(&events), ["replay", "started", "submitted"]);
+    }
+
+    #[test]
+    fn retry_from_result_screen_is_counted() {
+        let events = run(vec![
+            menu(),
+            playing([0; 6], 1.0).with_replay(false),
+            result_screen([300, 100, 0, 0, 0, 0]),
+            playing([0; 6], 1.0).with_replay(false),
+            playing([30, 10, 0, 0, 0, 0], 1.0).with_replay(false),
+            result_screen([310, 90, 0, 0, 0, 0]),
+            menu(),
+        ]);
+        assert_eq!(
+            names(&events),
+            ["started", "submitted", "started", "submitted"]
+        );
+    }
+
+    #[test]
+    fn replay_from_result_screen_is_not_submitted() {
+        let events = run(vec![
+            menu(),
+            playing([0; 6], 1.0),
+            result_screen([300, 100, 0, 0, 0, 0]),
+            playing([0; 6], 1.0).with_replay(true),
+            result_screen([300, 100, 0, 0, 0, 0]),
+            menu(),
+        ]);
+        assert_eq!(names(&events), ["started", "submitted", "replay"]);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::db::{HistoryRange, history_overall_rating, select_history};
+use crate::graphx::{ExportFormat, export_format, export_pages, export_pdf};
+use crate::tr;
+use crate::ui::notify::notify;
+use crate::{HistoryExportWindow, MainWindow, SCORES_DATA};
+use chrono::NaiveDate;
+use log::Level;
+use slint::{ComponentHandle, SharedString};
+use tokio::task::spawn_blocking;
+
+/// 打开导出成绩历史的窗口，导出当前选择的玩家
+pub fn show_history_export(ui: &MainWindow) {
+    let export_window = match HistoryExportWindow::new() {
+        Ok(export_window) => export_window,
+        Err(e) => {
+            notify(Level::Error, tr!("无法打开导出窗口: {}", e));
+            return;
+        }
+    };
+    let player_name = ui.get_current_player_name();
+    export_window.set_player_name(player_name.clone());
+
+    let window_export = export_window.as_weak();
+    export_window.on_export(move || {
+        let Some(window) = window_export.upgrade() else {
+            return;
+        };
+        let range = match window.get_range_index() {
+            0 => HistoryRange::Top(window.get_top_count().max(1) as usize),
+            _ => match (
+                parse_date(&window.get_date_from()),
+                parse_date(&window.get_date_to()),
+            ) {
+                (Ok(from), Ok(to)) => HistoryRange::Dates { from, to },
+                _ => {
+                    notify(Level::Warn, tr!("日期格式应为YYYY-MM-DD"));
+                    return;
+                }
+            },
+        };
+        let page_size = window.get_page_size().max(1) as usize;
+        let (ratings, average_rating) = {
+            let player_scores = SCORES_DATA.lock().unwrap();
+            (
+                select_history(player_name.as_str(), &player_scores, &range),
+                history_overall_rating(player_name.as_str(), &player_scores),
+            )
+        };
+        if ratings.is_empty() {
+            notify(Level::Warn, tr!("没有符合条件的成绩"));
+            return;
+        }
+
+        window.set_exporting(true);
+        let player_name = player_name.clone();
+        let window_handle = window_export.clone();
+        tokio::spawn(async move {
+            let title = range.title();
+            let result = spawn_blocking(move || match export_format() {
+                ExportFormat::Jpeg => export_pages(
+                    player_name.as_str(),
+                    &title,
+                    &ratings,
+                    average_rating,
+                    page_size,
+                ),
+                ExportFormat::Pdf => {
+                    let cards = ratings.into_iter().enumerate().collect::<Vec<_>>();
+                    export_pdf(
+                        player_name.as_str(),
+                        Some(&title),
+                        &cards,
+                        average_rating,
+                        page_size,
+                    )
+                }
+            })
+            .await;
+            match result {
+                Ok(Ok(path)) => {
+                    notify(Level::Info, tr!("导出完成! 导出路径: {}", path.display()));
+                    if let Err(e) = open::that(&path) {
+                        notify(Level::Warn, tr!("无法打开 {}: {}", path.display(), e));
+                    }
+                }
+                Ok(Err(e)) => notify(Level::Error, tr!("导出失败: {}", e)),
+                Err(e) => notify(Level::Error, tr!("导出失败: {}", e)),
+            }
+            let _ = window_handle.upgrade_in_event_loop(|window| window.set_exporting(false));
+        });
+    });
+
+    if let Err(e) = export_window.show() {
+        notify(Level::Error, tr!("无法打开导出窗口: {}", e));
+    }
+}
+
+/// 解析日期，留空时为None
+fn parse_date(text: &SharedString) -> Result<Option<NaiveDate>, chrono::ParseError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").map(Some)
+}
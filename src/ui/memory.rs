@@ -0,0 +1,260 @@
+use rosu_mem::process::Process;
+use rosu_memory_lib::common::stable::memory::{game_state, is_watching_replay};
+use rosu_memory_lib::common::{GameMode, GameState};
+use rosu_memory_lib::reader::beatmap::BeatmapReader;
+use rosu_memory_lib::reader::common::OsuClientKind;
+use rosu_memory_lib::reader::gameplay::GameplayReader;
+use rosu_memory_lib::reader::resultscreen::ResultScreenReader;
+use rosu_memory_lib::reader::structs::State;
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+/// 结算界面中读取到的内容
+#[derive(Debug, Clone)]
+pub struct ResultScreenData {
+    pub mode: GameMode,
+    pub username: String,
+    /// 依次为 marv, perf, great, good, bad, miss
+    pub judgement_num: [u32; 6],
+    pub accuracy: f64,
+}
+
+/// 当前谱面的.osu文件路径和MD5
+#[derive(Debug, Clone)]
+pub struct BeatmapData {
+    pub path: PathBuf,
+    pub md5: String,
+}
+
+/// 实时模式需要的所有内存读取。读取失败时返回None，调用方在下一轮重试
+pub trait GameMemorySource {
+    fn game_state(&mut self) -> GameState;
+    fn gameplay_mods(&mut self) -> Option<u32>;
+    /// 游玩过程中的判定数量，顺序与`ResultScreenData::judgement_num`相同
+    fn gameplay_hits(&mut self) -> Option<[u32; 6]>;
+    fn gameplay_hp(&mut self) -> Option<f64>;
+    /// 当前的Playing是否为回放。无法判断时返回None，检测器按游玩处理
+    fn is_replay(&mut self) -> Option<bool>;
+    fn result_screen(&mut self) -> Option<ResultScreenData>;
+    fn beatmap(&mut self) -> Option<BeatmapData>;
+    /// 游戏进程是否还在运行，返回false时实时模式会重新等待osu!启动
+    fn is_alive(&mut self) -> bool {
+        true
+    }
+}
+
+/// 通过rosu-memory-lib读取osu!stable进程的内存
+pub struct StableMemorySource {
+    process: Process,
+    state: State,
+    /// 连续读取游戏状态失败的次数
+    failed_reads: u32,
+}
+
+/// 连续读取失败这么多次后认为osu!已经关闭
+const MAX_FAILED_READS: u32 = 10;
+
+impl StableMemorySource {
+    pub fn new(state: State, process: Process) -> Self {
+        StableMemorySource {
+            process,
+            state,
+            failed_reads: 0,
+        }
+    }
+}
+
+impl GameMemorySource for StableMemorySource {
+    fn game_state(&mut self) -> GameState {
+        match game_state(&self.process, &mut self.state) {
+            Ok(gamestate) => {
+                self.failed_reads = 0;
+                gamestate
+            }
+            Err(_) => {
+                self.failed_reads += 1;
+                GameState::Unknown
+            }
+        }
+    }
+
+    fn gameplay_mods(&mut self) -> Option<u32> {
+        let mut gameplay_reader =
+            GameplayReader::new(&self.process, &mut self.state, OsuClientKind::Stable);
+        gameplay_reader.mods().ok()
+    }
+
+    fn gameplay_hits(&mut self) -> Option<[u32; 6]> {
+        let mut gameplay_reader =
+            GameplayReader::new(&self.process, &mut self.state, OsuClientKind::Stable);
+        Some([
+            gameplay_reader.hits_geki().ok()? as u32,
+            gameplay_reader.hits_300().ok()? as u32,
+            gameplay_reader.hits_katu().ok()? as u32,
+            gameplay_reader.hits_100().ok()? as u32,
+            gameplay_reader.hits_50().ok()? as u32,
+            gameplay_reader.hits_miss().ok()? as u32,
+        ])
+    }
+
+    fn gameplay_hp(&mut self) -> Option<f64> {
+        let mut gameplay_reader =
+            GameplayReader::new(&self.process, &mut self.state, OsuClientKind::Stable);
+        gameplay_reader.hp().ok()
+    }
+
+    fn is_replay(&mut self) -> Option<bool> {
+        is_watching_replay(&self.process, &mut self.state).ok()
+    }
+
+    fn result_screen(&mut self) -> Option<ResultScreenData> {
+        let mut resultscreen_reader =
+            ResultScreenReader::new(&self.process, &mut self.state, OsuClientKind::Stable);
+        let mode = match resultscreen_reader.mode() {
+            Ok(mode) => mode,
+            Err(e) => {
+                log::debug!("Failed to read result screen: {e}");
+                return None;
+            }
+        };
+        let judgement_num = [
+            resultscreen_reader.hits_geki().unwrap_or(0) as u32,
+            resultscreen_reader.hits_300().unwrap_or(0) as u32,
+            resultscreen_reader.hits_katu().unwrap_or(0) as u32,
+            resultscreen_reader.hits_100().unwrap_or(0) as u32,
+            resultscreen_reader.hits_50().unwrap_or(0) as u32,
+            resultscreen_reader.hits_miss().unwrap_or(0) as u32,
+        ];
+        Some(ResultScreenData {
+            mode,
+            username: resultscreen_reader.username().unwrap_or_default(),
+            judgement_num,
+            accuracy: resultscreen_reader.accuracy().unwrap_or(0.0),
+        })
+    }
+
+    fn beatmap(&mut self) -> Option<BeatmapData> {
+        let mut beatmap_reader =
+            BeatmapReader::new(&self.process, &mut self.state, OsuClientKind::Stable).ok()?;
+        let path = beatmap_reader.path().ok()?;
+        let md5 = beatmap_reader.md5().ok()?;
+        Some(BeatmapData { path, md5 })
+    }
+
+    fn is_alive(&mut self) -> bool {
+        self.failed_reads < MAX_FAILED_READS
+    }
+}
+
+/// 一次轮询时游戏内存中的内容
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub struct ScriptedFrame {
+    pub state: GameState,
+    pub mods: Option<u32>,
+    pub hits: Option<[u32; 6]>,
+    pub hp: Option<f64>,
+    pub replay: Option<bool>,
+    pub result_screen: Option<ResultScreenData>,
+    pub beatmap: Option<BeatmapData>,
+}
+
+#[cfg(test)]
+impl ScriptedFrame {
+    pub fn new(state: GameState) -> Self {
+        ScriptedFrame {
+            state,
+            mods: None,
+            hits: None,
+            hp: None,
+            replay: None,
+            result_screen: None,
+            beatmap: None,
+        }
+    }
+
+    pub fn with_mods(mut self, mods: u32) -> Self {
+        self.mods = Some(mods);
+        self
+    }
+
+    pub fn with_hits(mut self, hits: [u32; 6], hp: f64) -> Self {
+        self.hits = Some(hits);
+        self.hp = Some(hp);
+        self
+    }
+
+    pub fn with_beatmap(mut self, beatmap: BeatmapData) -> Self {
+        self.beatmap = Some(beatmap);
+        self
+    }
+
+    pub fn with_replay(mut self, replay: bool) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    pub fn with_result(mut self, result_screen: ResultScreenData, beatmap: BeatmapData) -> Self {
+        self.result_screen = Some(result_screen);
+        self.beatmap = Some(beatmap);
+        self
+    }
+}
+
+/// 按顺序重放预先写好的游戏状态，不需要运行osu!。
+/// 每次调用`game_state`前进一帧，其他读取返回当前帧的内容；播放完毕后一直处于Unknown状态
+#[cfg(test)]
+pub struct ScriptedMemorySource {
+    frames: VecDeque<ScriptedFrame>,
+    current: Option<ScriptedFrame>,
+}
+
+#[cfg(test)]
+impl ScriptedMemorySource {
+    pub fn new(frames: impl IntoIterator<Item = ScriptedFrame>) -> Self {
+        ScriptedMemorySource {
+            frames: frames.into_iter().collect(),
+            current: None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+#[cfg(test)]
+impl GameMemorySource for ScriptedMemorySource {
+    fn game_state(&mut self) -> GameState {
+        self.current = self.frames.pop_front();
+        self.current
+            .as_ref()
+            .map(|frame| frame.state)
+            .unwrap_or(GameState::Unknown)
+    }
+
+    fn gameplay_mods(&mut self) -> Option<u32> {
+        self.current.as_ref()?.mods
+    }
+
+    fn gameplay_hits(&mut self) -> Option<[u32; 6]> {
+        self.current.as_ref()?.hits
+    }
+
+    fn gameplay_hp(&mut self) -> Option<f64> {
+        self.current.as_ref()?.hp
+    }
+
+    fn is_replay(&mut self) -> Option<bool> {
+        self.current.as_ref()?.replay
+    }
+
+    fn result_screen(&mut self) -> Option<ResultScreenData> {
+        self.current.as_ref()?.result_screen.clone()
+    }
+
+    fn beatmap(&mut self) -> Option<BeatmapData> {
+        self.current.as_ref()?.beatmap.clone()
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{MainWindow, Notification};
+use log::Level;
+use slint::{Model, ModelRc, SharedString, VecModel, Weak};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+
+/// 界面上同时显示的通知数量上限，超出时移除最早的通知
+const MAX_NOTIFICATIONS: usize = 5;
+
+static NOTIFY_SENDER: OnceLock<UnboundedSender<(Level, String)>> = OnceLock::new();
+static NEXT_ID: AtomicI32 = AtomicI32::new(0);
+
+/// 记录日志并在界面右下角显示通知，可以在任意线程调用
+pub fn notify(level: Level, message: impl Into<String>) {
+    let message = message.into();
+    log::log!(level, "{message}");
+    if let Some(sender) = NOTIFY_SENDER.get() {
+        let _ = sender.send((level, message));
+    }
+}
+
+/// 启动将通知转发到界面的任务，需要在tokio运行时中调用
+pub fn init_notifications(ui_handle: Weak<MainWindow>) {
+    let (sender, mut receiver) = unbounded_channel();
+    if NOTIFY_SENDER.set(sender).is_err() {
+        return;
+    }
+    tokio::spawn(async move {
+        while let Some((level, message)) = receiver.recv().await {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+                push_notification(&ui, id, level, message);
+            });
+            if let Some(timeout) = auto_dismiss_after(level) {
+                let ui_handle = ui_handle.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(timeout).await;
+                    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+                        dismiss_notification(&ui, id);
+                    });
+                });
+            }
+        }
+    });
+}
+
+/// 错误需要手动关闭，其他通知一段时间后自动消失
+fn auto_dismiss_after(level: Level) -> Option<Duration> {
+    match level {
+        Level::Error => None,
+        Level::Warn => Some(Duration::from_secs(10)),
+        _ => Some(Duration::from_secs(5)),
+    }
+}
+
+fn push_notification(ui: &MainWindow, id: i32, level: Level, message: String) {
+    let notification = Notification {
+        id,
+        level: match level {
+            Level::Error => 2,
+            Level::Warn => 1,
+            _ => 0,
+        },
+        text: SharedString::from(message),
+        count: 1,
+    };
+    let notifications = ui.get_notifications();
+    let Some(model) = notifications
+        .as_any()
+        .downcast_ref::<VecModel<Notification>>()
+    else {
+        ui.set_notifications(ModelRc::new(VecModel::from(vec![notification])));
+        return;
+    };
+
+    // 相同内容的通知合并，并以新的id重新计时
+    if let Some((index, existing)) = model
+        .iter()
+        .enumerate()
+        .find(|(_, existing)| existing.text == notification.text)
+    {
+        model.set_row_data(
+            index,
+            Notification {
+                count: existing.count + 1,
+                ..notification
+            },
+        );
+        return;
+    }
+    model.push(notification);
+    while model.row_count() > MAX_NOTIFICATIONS {
+        model.remove(0);
+    }
+}
+
+pub fn dismiss_notification(ui: &MainWindow, id: i32) {
+    let notifications = ui.get_notifications();
+    if let Some(model) = notifications
+        .as_any()
+        .downcast_ref::<VecModel<Notification>>()
+        && let Some(index) = model.iter().position(|n| n.id == id)
+    {
+        model.remove(index);
+    }
+}
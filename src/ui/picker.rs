@@ -0,0 +1,116 @@
+use crate::db::RatingInfo;
+use crate::tr;
+use crate::ui::callbacks::player_cards;
+use crate::ui::notify::notify;
+use crate::{MainWindow, SCORES_DATA, ScorePickerWindow};
+use log::Level;
+use slint::{ComponentHandle, Model, ModelRc, SharedString, StandardListViewItem, VecModel};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// 选择窗口中的成绩和当前的筛选结果，只在UI线程中使用
+#[derive(Default)]
+struct PickerState {
+    /// (卡片序号, 显示的文字)
+    entries: Vec<(usize, String)>,
+    /// 列表中每一行对应的卡片序号
+    visible: Vec<usize>,
+    filter: String,
+}
+
+impl PickerState {
+    fn refresh(&mut self, window: &ScorePickerWindow) {
+        let filter = self.filter.to_lowercase();
+        let (visible, items): (Vec<_>, Vec<_>) = self
+            .entries
+            .iter()
+            .filter(|(_, label)| filter.is_empty() || label.to_lowercase().contains(&filter))
+            .map(|(index, label)| (*index, StandardListViewItem::from(label.as_str())))
+            .unzip();
+        self.visible = visible;
+        window.set_items(ModelRc::new(VecModel::from(items)));
+    }
+}
+
+/// 从玩家的全部成绩中选择成绩加入成绩表，已经在成绩表中的成绩不显示
+pub fn show_score_picker(ui: &MainWindow) {
+    let player_name = ui.get_current_player_name();
+    let cards = {
+        let scores = SCORES_DATA.lock().unwrap();
+        player_cards(player_name.as_str(), &scores)
+    };
+    let Some(cards) = cards else {
+        notify(Level::Warn, tr!("当前玩家没有成绩"));
+        return;
+    };
+    let picker_window = match ScorePickerWindow::new() {
+        Ok(picker_window) => picker_window,
+        Err(e) => {
+            notify(Level::Error, tr!("无法打开成绩列表: {}", e));
+            return;
+        }
+    };
+
+    let shown = ui
+        .get_score_tiles()
+        .iter()
+        .map(|tile| tile.index as usize)
+        .collect::<HashSet<_>>();
+    let state = Rc::new(RefCell::new(PickerState {
+        entries: cards
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !shown.contains(index))
+            .map(|(index, info)| (index, entry_label(index, info)))
+            .collect(),
+        ..Default::default()
+    }));
+    state.borrow_mut().refresh(&picker_window);
+
+    let state_filter = state.clone();
+    let window_filter = picker_window.as_weak();
+    picker_window.on_filter_changed(move |text| {
+        let Some(window) = window_filter.upgrade() else {
+            return;
+        };
+        let mut state = state_filter.borrow_mut();
+        state.filter = text.to_string();
+        state.refresh(&window);
+    });
+
+    let ui_add = ui.as_weak();
+    let window_add = picker_window.as_weak();
+    picker_window.on_add(move |row| {
+        let (Some(ui), Some(window)) = (ui_add.upgrade(), window_add.upgrade()) else {
+            return;
+        };
+        let mut state = state.borrow_mut();
+        let Some(index) = state.visible.get(row as usize).copied() else {
+            return;
+        };
+        ui.invoke_added(index as i32);
+        state.entries.retain(|(other, _)| *other != index);
+        state.refresh(&window);
+        window.set_current_item(-1);
+    });
+
+    if let Err(e) = picker_window.show() {
+        notify(Level::Error, tr!("无法打开成绩列表: {}", e));
+    }
+}
+
+fn entry_label(index: usize, info: &RatingInfo) -> String {
+    let beatmap_info = &info.map_info.info;
+    let mods = info.score_info.mods_text();
+    format!(
+        "#{}  {} - {} [{}] ({})  {}  Rating {:.2}",
+        index + 1,
+        beatmap_info.artist,
+        beatmap_info.title,
+        beatmap_info.version,
+        beatmap_info.creator,
+        if mods.is_empty() { "NM" } else { mods.as_str() },
+        info.rating
+    )
+}
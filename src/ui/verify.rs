@@ -0,0 +1,84 @@
+use crate::db::{VerifyReport, default_dialog_location, list_plays};
+use crate::error::AppError;
+use crate::graphx::read_manifest;
+use crate::tr;
+use crate::ui::notify::notify;
+use crate::{REALTIME_DATA, SCORES_DATA, VerifyWindow};
+use log::Level;
+use native_dialog::DialogBuilder;
+use slint::{ComponentHandle, ModelRc, SharedString, StandardListViewItem, VecModel};
+use std::env;
+use std::path::PathBuf;
+use tokio::task::spawn_blocking;
+
+/// 选择导出的图片或PDF，用本地的成绩核对其中的成绩清单
+pub fn verify_export() {
+    let location = env::current_dir()
+        .map(|dir| dir.join("export"))
+        .ok()
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(default_dialog_location);
+    let path = match DialogBuilder::file()
+        .set_location(&location)
+        .set_title(tr!("选择导出的图片或PDF"))
+        .add_filter("JPEG / PDF", ["jpg", "jpeg", "pdf"])
+        .open_single_file()
+        .show()
+    {
+        Ok(Some(path)) => path,
+        Ok(None) => return,
+        Err(e) => {
+            notify(Level::Error, AppError::from(e).to_string());
+            return;
+        }
+    };
+    let manifest = match read_manifest(&path) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            notify(Level::Warn, e.to_string());
+            return;
+        }
+    };
+
+    let mut plays = list_plays("[All Players]", &SCORES_DATA.lock().unwrap());
+    plays.extend(REALTIME_DATA.lock().unwrap().iter().cloned());
+    // 重新计算需要读取谱面，在后台进行
+    tokio::spawn(async move {
+        let report = match spawn_blocking(move || manifest.verify(&plays)).await {
+            Ok(report) => report,
+            Err(e) => {
+                log::error!("Failed to verify {}: {e}", path.display());
+                return;
+            }
+        };
+        log::info!(
+            "Verified {}: {} cards, {} problems, hash valid: {}",
+            path.display(),
+            report.cards.len(),
+            report.problem_count(),
+            report.hash_valid
+        );
+        let _ = slint::invoke_from_event_loop(move || show_report(path, report));
+    });
+}
+
+fn show_report(path: PathBuf, report: VerifyReport) {
+    let verify_window = match VerifyWindow::new() {
+        Ok(verify_window) => verify_window,
+        Err(e) => {
+            notify(Level::Error, tr!("无法打开验证结果: {}", e));
+            return;
+        }
+    };
+    let items = report
+        .item_lines()
+        .into_iter()
+        .map(|line| StandardListViewItem::from(SharedString::from(line)))
+        .collect::<Vec<_>>();
+    verify_window.set_file_name(SharedString::from(path.display().to_string()));
+    verify_window.set_summary(SharedString::from(report.summary()));
+    verify_window.set_items(ModelRc::new(VecModel::from(items)));
+    if let Err(e) = verify_window.show() {
+        notify(Level::Error, tr!("无法打开验证结果: {}", e));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::db::{
+    INGESTION_REPORT, IngestionProgress, RatingInfo, extract_new_ratings, imported_ratings,
+    merge_ratings, player_list_name, prepare_ratings, table_size,
+};
+use crate::ui::callbacks::sync_player_models;
+use crate::{MainWindow, SCORES_DATA};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use slint::Weak;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::spawn_blocking;
+
+// osu!写入数据库时会在短时间内触发很多次事件，等安静下来之后再读取
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(1500);
+
+static OSU_WATCHER: LazyLock<Mutex<Option<RecommendedWatcher>>> =
+    LazyLock::new(|| Mutex::new(None));
+// 每次停止监视时增加，之前开始的刷新得到的结果会被丢弃
+static WATCH_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// 监视到的变化，取一段时间内最大的一个
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Change {
+    /// scores.db或回放，只需计算新增的成绩
+    Scores,
+    /// osu!.db，谱面列表和状态可能变化，需要完整读取
+    Listing,
+}
+
+/// 停止监视，还没有完成的刷新不再写入SCORES_DATA。选择其他文件夹时调用，返回新的代数
+pub fn stop_watching() -> u64 {
+    *OSU_WATCHER.lock().unwrap() = None;
+    WATCH_GENERATION.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+fn is_current(generation: u64) -> bool {
+    WATCH_GENERATION.load(Ordering::SeqCst) == generation
+}
+
+/// 监视osu!目录下的scores.db, osu!.db以及Data/r中的回放，发生变化时自动刷新成绩。
+/// 再次调用会替换掉之前的监视器，旧的刷新任务也会随之结束
+pub fn watch_osu_dir(osu_exe_dir: String, ui_handle: Weak<MainWindow>) -> notify::Result<()> {
+    let generation = stop_watching();
+    let osu_path = PathBuf::from(&osu_exe_dir);
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Change>();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        if let Ok(event) = res
+            && let Some(change) = classify(&event)
+        {
+            let _ = sender.send(change);
+        }
+    })?;
+    watcher.watch(&osu_path, RecursiveMode::NonRecursive)?;
+    let replay_dir = osu_path.join("Data").join("r");
+    if replay_dir.is_dir() {
+        watcher.watch(&replay_dir, RecursiveMode::NonRecursive)?;
+    }
+    *OSU_WATCHER.lock().unwrap() = Some(watcher);
+
+    tokio::spawn(async move {
+        while let Some(mut change) = receiver.recv().await {
+            loop {
+                match tokio::time::timeout(DEBOUNCE_DURATION, receiver.recv()).await {
+                    Ok(Some(next)) => change = change.max(next),
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+            if !is_current(generation) {
+                return;
+            }
+            refresh_ratings(&osu_exe_dir, change, generation, ui_handle.clone()).await;
+        }
+    });
+    Ok(())
+}
+
+fn classify(event: &Event) -> Option<Change> {
+    if !matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) {
+        return None;
+    }
+    event
+        .paths
+        .iter()
+        .filter_map(|p| match p.file_name().and_then(|n| n.to_str()) {
+            Some("osu!.db") => Some(Change::Listing),
+            Some("scores.db") => Some(Change::Scores),
+            Some(name) if name.ends_with(".osr") => Some(Change::Scores),
+            _ => None,
+        })
+        .max()
+}
+
+/// 重新读取成绩并原地更新玩家列表，只有当前玩家成绩表范围内的成绩发生变化时才重新生成卡片。
+/// 只有scores.db或回放变化时只计算新增的成绩，`generation`过期时丢弃结果
+async fn refresh_ratings(
+    osu_exe_dir: &str,
+    change: Change,
+    generation: u64,
+    ui_handle: Weak<MainWindow>,
+) {
+    let changed_players = match change {
+        Change::Scores => refresh_new_scores(osu_exe_dir, generation).await,
+        Change::Listing => reload_all(osu_exe_dir, generation).await,
+    };
+    if let Some(changed_players) = changed_players {
+        sync_player_models(changed_players, ui_handle);
+    }
+}
+
+async fn refresh_new_scores(osu_exe_dir: &str, generation: u64) -> Option<HashSet<String>> {
+    let dir = osu_exe_dir.to_string();
+    let known = SCORES_DATA
+        .lock()
+        .unwrap()
+        .get("[Recent 30]")
+        .cloned()
+        .unwrap_or_default();
+    let new_ratings = match spawn_blocking(move || extract_new_ratings(&dir, &known)).await {
+        Ok(Ok(new_ratings)) => new_ratings,
+        Ok(Err(e)) => {
+            // 数据库可能还在写入中，等下一次变化再读取
+            log::warn!("Failed to refresh ratings: {e}");
+            return None;
+        }
+        Err(e) => {
+            log::error!("Failed to refresh ratings: {e}");
+            return None;
+        }
+    };
+    if new_ratings.is_empty() {
+        return None;
+    }
+    log::info!("Found {} new scores", new_ratings.len());
+
+    let mut scores = SCORES_DATA.lock().unwrap();
+    if !is_current(generation) {
+        return None;
+    }
+    let affected = new_ratings
+        .iter()
+        .flat_map(|info| {
+            [
+                "[Recent 30]".to_string(),
+                "[All Players]".to_string(),
+                player_list_name(info.score_info.player.clone()),
+            ]
+        })
+        .collect::<HashSet<_>>();
+    let old_lists = affected
+        .into_iter()
+        .map(|name| {
+            let old = scores.get(&name).cloned().unwrap_or_default();
+            (name, old)
+        })
+        .collect::<HashMap<_, _>>();
+    merge_ratings(&mut scores, &new_ratings);
+    Some(
+        old_lists
+            .into_iter()
+            .filter(|(name, old)| scores.get(name).is_some_and(|new| !is_same_table(old, new)))
+            .map(|(name, _)| name)
+            .collect(),
+    )
+}
+
+async fn reload_all(osu_exe_dir: &str, generation: u64) -> Option<HashSet<String>> {
+    let dir = osu_exe_dir.to_string();
+    // 后台刷新不显示进度
+    let progress = IngestionProgress::new();
+    let (mut data, report) = match spawn_blocking(move || prepare_ratings(&dir, &progress)).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => {
+            // 数据库可能还在写入中，等下一次变化再读取
+            log::warn!("Failed to refresh ratings: {e}");
+            return None;
+        }
+        Err(e) => {
+            log::error!("Failed to refresh ratings: {e}");
+            return None;
+        }
+    };
+
+    merge_ratings(&mut data, &imported_ratings());
+    let mut scores = SCORES_DATA.lock().unwrap();
+    if !is_current(generation) {
+        return None;
+    }
+    *INGESTION_REPORT.lock().unwrap() = report;
+    let changed_players = data
+        .iter()
+        .filter(|(name, ratings)| {
+            scores
+                .get(*name)
+                .is_none_or(|old| !is_same_table(old, ratings))
+        })
+        .map(|(name, _)| name.clone())
+        .collect::<HashSet<_>>();
+    *scores = data;
+    Some(changed_players)
+}
+
+fn is_same_table(old: &[RatingInfo], new: &[RatingInfo]) -> bool {
+    let size = table_size();
+    old.len().min(size) == new.len().min(size)
+        && old
+            .iter()
+            .zip(new.iter())
+            .take(size)
+            .all(|(a, b)| a.is_same_score(b) && a.rating == b.rating)
+}